@@ -0,0 +1,311 @@
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::time::Duration;
+use embedded_hal::can::ErrorKind;
+use embedded_hal::digital::v2::PinState;
+
+use ross_protocol::convert_packet::ConvertPacket;
+use ross_protocol::packet::Packet;
+
+use crate::{
+    CanExpectation, CanFrame, DelayExpectation, EventPacket, Expectation, InputPinExpectation,
+    InterfaceExpectation, Mock, MockInterfaceError, OutputPinExpectation, PacketMatcher,
+    PwmExpectation, TimerExpectation, Times, TrackerHandle,
+};
+
+/// Adds an expectation for a mock to its tracker, see `Mock::expect`
+#[derive(Debug)]
+pub struct ExpectationBuilder<'a, H: TrackerHandle> {
+    mock: &'a Mock<H>,
+}
+
+impl<'a, H: TrackerHandle> ExpectationBuilder<'a, H> {
+    pub(crate) fn new(mock: &'a Mock<H>) -> Self {
+        Self { mock }
+    }
+
+    pub fn expectation(self, expectation: Expectation) -> ExpectationHandle<H> {
+        let tracker = self.mock.expectation_tracker().clone();
+        let index = self.mock.get_index();
+        let position =
+            tracker.with(|tracker| tracker.push_expectation(index, expectation, Times::Exactly(1)));
+
+        ExpectationHandle { tracker, position }
+    }
+
+    pub fn send(self, packet: Packet) -> ExpectationHandle<H> {
+        self.interface(InterfaceExpectation::SentPacket(packet))
+    }
+
+    pub fn receive(self, packet: Packet) -> ExpectationHandle<H> {
+        self.interface(InterfaceExpectation::ReceivedPacket(packet))
+    }
+
+    pub fn send_matching(self, matchers: Vec<PacketMatcher>) -> ExpectationHandle<H> {
+        self.interface(InterfaceExpectation::SentPacketMatching(matchers))
+    }
+
+    pub fn send_event<E: ConvertPacket<E> + Debug>(self, event: &E) -> ExpectationHandle<H> {
+        self.interface(InterfaceExpectation::SentEvent(EventPacket::new(event)))
+    }
+
+    pub fn receive_event<E: ConvertPacket<E> + Debug>(self, event: &E) -> ExpectationHandle<H> {
+        self.interface(InterfaceExpectation::ReceivedEvent(EventPacket::new(event)))
+    }
+
+    pub fn send_error(self, error: MockInterfaceError) -> ExpectationHandle<H> {
+        self.interface(InterfaceExpectation::SendError(error))
+    }
+
+    pub fn receive_error(self, error: MockInterfaceError) -> ExpectationHandle<H> {
+        self.interface(InterfaceExpectation::ReceiveError(error))
+    }
+
+    pub fn is_high(self) -> ExpectationHandle<H> {
+        self.expectation(Expectation::InputPin(InputPinExpectation::IsHigh))
+    }
+
+    pub fn is_low(self) -> ExpectationHandle<H> {
+        self.expectation(Expectation::InputPin(InputPinExpectation::IsLow))
+    }
+
+    pub fn set_high(self) -> ExpectationHandle<H> {
+        self.output_pin(OutputPinExpectation::SetHigh)
+    }
+
+    pub fn set_low(self) -> ExpectationHandle<H> {
+        self.output_pin(OutputPinExpectation::SetLow)
+    }
+
+    pub fn is_set_high(self) -> ExpectationHandle<H> {
+        self.output_pin(OutputPinExpectation::IsSetHigh)
+    }
+
+    pub fn is_set_low(self) -> ExpectationHandle<H> {
+        self.output_pin(OutputPinExpectation::IsSetLow)
+    }
+
+    pub fn toggle(self) -> ExpectationHandle<H> {
+        self.output_pin(OutputPinExpectation::Toggle)
+    }
+
+    pub fn toggle_to(self, output_state: PinState) -> ExpectationHandle<H> {
+        self.output_pin(OutputPinExpectation::ToggleTo(output_state))
+    }
+
+    pub fn transmit_frame(self, frame: CanFrame) -> ExpectationHandle<H> {
+        self.can(CanExpectation::Transmitted(frame))
+    }
+
+    pub fn receive_frame(self, frame: CanFrame) -> ExpectationHandle<H> {
+        self.can(CanExpectation::Received(frame))
+    }
+
+    pub fn mailbox_full(self, dequeued_frame: CanFrame) -> ExpectationHandle<H> {
+        self.can(CanExpectation::MailboxFull(dequeued_frame))
+    }
+
+    pub fn transmit_blocked(self) -> ExpectationHandle<H> {
+        self.can(CanExpectation::TransmitBlocked)
+    }
+
+    pub fn transmit_error(self, error: ErrorKind) -> ExpectationHandle<H> {
+        self.can(CanExpectation::TransmitError(error))
+    }
+
+    pub fn receive_frame_error(self, error: ErrorKind) -> ExpectationHandle<H> {
+        self.can(CanExpectation::ReceiveError(error))
+    }
+
+    pub fn delay(self, duration: Duration) -> ExpectationHandle<H> {
+        self.expectation(Expectation::Delay(DelayExpectation::Exactly(duration)))
+    }
+
+    pub fn delay_at_least(self, min: Duration) -> ExpectationHandle<H> {
+        self.expectation(Expectation::Delay(DelayExpectation::AtLeast(min)))
+    }
+
+    pub fn delay_at_most(self, max: Duration) -> ExpectationHandle<H> {
+        self.expectation(Expectation::Delay(DelayExpectation::AtMost(max)))
+    }
+
+    pub fn delay_between(self, min: Duration, max: Duration) -> ExpectationHandle<H> {
+        self.expectation(Expectation::Delay(DelayExpectation::Between(min, max)))
+    }
+
+    /// Timer `start` with the given period
+    pub fn start(self, period: Duration) -> ExpectationHandle<H> {
+        self.expectation(Expectation::Timer(TimerExpectation::Started(period)))
+    }
+
+    /// Timer `wait` that returns before the deadline is reached on the virtual clock
+    pub fn expire(self) -> ExpectationHandle<H> {
+        self.expectation(Expectation::Timer(TimerExpectation::Expired))
+    }
+
+    pub fn enable(self) -> ExpectationHandle<H> {
+        self.pwm(PwmExpectation::Enable)
+    }
+
+    pub fn disable(self) -> ExpectationHandle<H> {
+        self.pwm(PwmExpectation::Disable)
+    }
+
+    pub fn set_duty(self, duty: u16) -> ExpectationHandle<H> {
+        self.pwm(PwmExpectation::SetDuty(duty))
+    }
+
+    pub fn set_duty_between(self, min: u16, max: u16) -> ExpectationHandle<H> {
+        self.pwm(PwmExpectation::SetDutyBetween(min, max))
+    }
+
+    /// `set_duty` with a duty cycle at most `tolerance` away from the given one
+    pub fn set_duty_within(self, duty: u16, tolerance: u16) -> ExpectationHandle<H> {
+        self.set_duty_between(
+            duty.saturating_sub(tolerance),
+            duty.saturating_add(tolerance),
+        )
+    }
+
+    pub fn get_duty(self, duty: u16) -> ExpectationHandle<H> {
+        self.pwm(PwmExpectation::GetDuty(duty))
+    }
+
+    pub fn get_max_duty(self, max_duty: u16) -> ExpectationHandle<H> {
+        self.pwm(PwmExpectation::GetMaxDuty(max_duty))
+    }
+
+    fn interface(self, expectation: InterfaceExpectation) -> ExpectationHandle<H> {
+        self.expectation(Expectation::Interface(expectation))
+    }
+
+    fn output_pin(self, expectation: OutputPinExpectation) -> ExpectationHandle<H> {
+        self.expectation(Expectation::OutputPin(expectation))
+    }
+
+    fn can(self, expectation: CanExpectation) -> ExpectationHandle<H> {
+        self.expectation(Expectation::Can(expectation))
+    }
+
+    fn pwm(self, expectation: PwmExpectation) -> ExpectationHandle<H> {
+        self.expectation(Expectation::Pwm(expectation))
+    }
+}
+
+/// An added expectation, expected exactly once unless changed
+#[derive(Debug)]
+pub struct ExpectationHandle<H: TrackerHandle> {
+    tracker: H,
+    position: usize,
+}
+
+impl<H: TrackerHandle> ExpectationHandle<H> {
+    pub fn times(self, times: usize) -> Self {
+        self.with_times(Times::Exactly(times))
+    }
+
+    pub fn at_least(self, times: usize) -> Self {
+        self.with_times(Times::AtLeast(times))
+    }
+
+    pub fn at_most(self, times: usize) -> Self {
+        self.with_times(Times::AtMost(times))
+    }
+
+    pub fn any_times(self) -> Self {
+        self.with_times(Times::Any)
+    }
+
+    pub fn with_times(self, times: Times) -> Self {
+        self.tracker
+            .with(|tracker| tracker.set_times(self.position, times));
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+    use embedded_hal::digital::v2::{InputPin, OutputPin};
+    use ross_protocol::interface::{Interface, InterfaceError};
+
+    use crate::ExpectationTracker;
+
+    #[test]
+    fn fluent_expectations_test() {
+        let tracker = ExpectationTracker::new();
+        let mut interface = ExpectationTracker::mock(tracker.clone());
+        let button = ExpectationTracker::mock(tracker.clone());
+        let mut relay = ExpectationTracker::mock(tracker.clone());
+
+        interface
+            .expect()
+            .receive_error(MockInterfaceError::NoPacketReceived)
+            .times(2);
+        button.expect().is_high();
+        relay.expect().set_high().times(2);
+        interface.expect().send(Packet {
+            is_error: false,
+            device_address: 0x1111,
+            data: vec![0x01],
+        });
+        interface.expect().receive(Packet {
+            is_error: false,
+            device_address: 0x2222,
+            data: vec![],
+        });
+
+        assert!(matches!(
+            interface.try_get_packet(),
+            Err(InterfaceError::NoPacketReceived)
+        ));
+        assert!(matches!(
+            interface.try_get_packet(),
+            Err(InterfaceError::NoPacketReceived)
+        ));
+        assert!(button.is_high().unwrap());
+        relay.set_high().unwrap();
+        relay.set_high().unwrap();
+        interface
+            .try_send_packet(&Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x01],
+            })
+            .unwrap();
+        assert_eq!(interface.try_get_packet().unwrap().device_address, 0x2222);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn fluent_times_test() {
+        let tracker = ExpectationTracker::new();
+        let mut relay = ExpectationTracker::mock(tracker.clone());
+
+        relay.expect().set_high().at_least(1);
+        relay.expect().set_low().at_most(1);
+        relay.expect().toggle().any_times();
+
+        relay.set_high().unwrap();
+        relay.set_high().unwrap();
+        relay.set_high().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected exactly 2 calls, received 1")]
+    fn fluent_times_not_reached_test() {
+        let tracker = ExpectationTracker::new();
+        let mut relay = ExpectationTracker::mock(tracker.clone());
+
+        relay.expect().set_high().times(2);
+        relay.set_high().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+}
@@ -0,0 +1,173 @@
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use ross_protocol::interface::{Interface, InterfaceError};
+use ross_protocol::packet::Packet;
+
+use crate::Mock;
+
+/// Simulated bus delivering packets sent by one endpoint to all other endpoints
+#[derive(Debug)]
+pub struct VirtualBus {
+    queues: Vec<VecDeque<Packet>>,
+    observer: Option<Mock>,
+}
+
+impl VirtualBus {
+    pub fn new() -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            queues: vec![],
+            observer: None,
+        }))
+    }
+
+    pub fn endpoint(bus: Rc<RefCell<VirtualBus>>) -> BusEndpoint {
+        let index = bus.borrow().queues.len();
+        bus.borrow_mut().queues.push(VecDeque::new());
+        BusEndpoint::new(bus.clone(), index)
+    }
+
+    /// Verifies every packet sent on the bus as a `try_send_packet` call to the given mock
+    pub fn observe(&mut self, mock: Mock) {
+        self.observer = Some(mock);
+    }
+
+    fn send(&mut self, sender: usize, packet: &Packet) -> Result<(), InterfaceError> {
+        if let Some(ref mut observer) = self.observer {
+            observer.try_send_packet(packet)?;
+        }
+
+        for (index, queue) in self.queues.iter_mut().enumerate() {
+            if index != sender {
+                queue.push_back(packet.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn receive(&mut self, receiver: usize) -> Result<Packet, InterfaceError> {
+        self.queues[receiver]
+            .pop_front()
+            .ok_or(InterfaceError::NoPacketReceived)
+    }
+}
+
+/// Interface connected to a virtual bus
+#[derive(Debug)]
+pub struct BusEndpoint {
+    bus: Rc<RefCell<VirtualBus>>,
+    index: usize,
+}
+
+impl BusEndpoint {
+    fn new(bus: Rc<RefCell<VirtualBus>>, index: usize) -> Self {
+        Self { bus, index }
+    }
+
+    /// Number of packets waiting to be received by this endpoint
+    pub fn pending(&self) -> usize {
+        self.bus.borrow().queues[self.index].len()
+    }
+}
+
+impl Interface for BusEndpoint {
+    fn try_get_packet(&mut self) -> Result<Packet, InterfaceError> {
+        self.bus.borrow_mut().receive(self.index)
+    }
+
+    fn try_send_packet(&mut self, packet: &Packet) -> Result<(), InterfaceError> {
+        self.bus.borrow_mut().send(self.index, packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Expectation, ExpectationTracker, InterfaceExpectation};
+
+    #[test]
+    fn delivery_to_other_endpoints_test() {
+        let bus = VirtualBus::new();
+        let mut controller = VirtualBus::endpoint(bus.clone());
+        let mut relay = VirtualBus::endpoint(bus.clone());
+        let mut button = VirtualBus::endpoint(bus.clone());
+
+        let packet = Packet {
+            is_error: false,
+            device_address: 0x1111,
+            data: vec![0x11, 0x11, 0x11],
+        };
+
+        controller.try_send_packet(&packet).unwrap();
+
+        assert_eq!(controller.pending(), 0);
+        assert_eq!(relay.try_get_packet().unwrap(), packet);
+        assert_eq!(button.try_get_packet().unwrap(), packet);
+        assert!(matches!(
+            relay.try_get_packet(),
+            Err(InterfaceError::NoPacketReceived)
+        ));
+        assert!(matches!(
+            controller.try_get_packet(),
+            Err(InterfaceError::NoPacketReceived)
+        ));
+    }
+
+    #[test]
+    fn observed_traffic_test() {
+        let tracker = ExpectationTracker::new();
+        let observer = ExpectationTracker::named_mock(tracker.clone(), "bus");
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &observer,
+            Expectation::Interface(InterfaceExpectation::SentPacket(Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x11],
+            })),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &observer,
+            Expectation::Interface(InterfaceExpectation::SentPacket(Packet {
+                is_error: false,
+                device_address: 0x2222,
+                data: vec![0x22],
+            })),
+        );
+
+        let bus = VirtualBus::new();
+        bus.borrow_mut().observe(observer);
+
+        let mut controller = VirtualBus::endpoint(bus.clone());
+        let mut relay = VirtualBus::endpoint(bus.clone());
+
+        controller
+            .try_send_packet(&Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x11],
+            })
+            .unwrap();
+
+        let request = relay.try_get_packet().unwrap();
+
+        relay
+            .try_send_packet(&Packet {
+                is_error: false,
+                device_address: 0x2222,
+                data: request.data.iter().map(|byte| byte * 2).collect(),
+            })
+            .unwrap();
+
+        assert_eq!(controller.try_get_packet().unwrap().device_address, 0x2222);
+
+        tracker.borrow_mut().done();
+    }
+}
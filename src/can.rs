@@ -0,0 +1,410 @@
+use alloc::vec::Vec;
+use bxcan::{Data, ExtendedId, Frame as BxFrame, Id as BxId, StandardId};
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use embedded_hal::can::{
+    Can, ExtendedId as HalExtendedId, Frame as HalFrame, Id, StandardId as HalStandardId,
+};
+use nb::block;
+
+use ross_protocol::frame::Frame;
+use ross_protocol::interface::can::CanError;
+use ross_protocol::interface::{Interface, InterfaceError};
+use ross_protocol::packet::{Packet, PacketBuilder};
+
+/// CAN frame handled by the frame level mock, convertible to and from bxcan frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanFrame(pub BxFrame);
+
+impl CanFrame {
+    /// Converts all frames of a packet, as transmitted by the ross_protocol CAN interface
+    pub fn from_packet(packet: &Packet) -> Vec<Self> {
+        packet
+            .to_frames()
+            .iter()
+            .map(|frame| CanFrame(frame.to_bxcan_frame()))
+            .collect()
+    }
+}
+
+impl From<BxFrame> for CanFrame {
+    fn from(frame: BxFrame) -> Self {
+        CanFrame(frame)
+    }
+}
+
+impl From<CanFrame> for BxFrame {
+    fn from(frame: CanFrame) -> Self {
+        frame.0
+    }
+}
+
+impl HalFrame for CanFrame {
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        Some(CanFrame(BxFrame::new_data(
+            to_bxcan_id(id.into()),
+            Data::new(data)?,
+        )))
+    }
+
+    fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+        if dlc > 8 {
+            return None;
+        }
+
+        Some(CanFrame(BxFrame::new_remote(
+            to_bxcan_id(id.into()),
+            dlc as u8,
+        )))
+    }
+
+    fn is_extended(&self) -> bool {
+        self.0.is_extended()
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        self.0.is_remote_frame()
+    }
+
+    fn id(&self) -> Id {
+        match self.0.id() {
+            BxId::Standard(id) => Id::Standard(HalStandardId::new(id.as_raw()).unwrap()),
+            BxId::Extended(id) => Id::Extended(HalExtendedId::new(id.as_raw()).unwrap()),
+        }
+    }
+
+    fn dlc(&self) -> usize {
+        self.0.dlc() as usize
+    }
+
+    fn data(&self) -> &[u8] {
+        self.0.data().map(|data| &data[..]).unwrap_or(&[])
+    }
+}
+
+/// Writes the frame in candump notation, e.g. `18001111#0102` or `123#R`
+impl Display for CanFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.0.id() {
+            BxId::Standard(id) => write!(f, "{:03X}#", id.as_raw())?,
+            BxId::Extended(id) => write!(f, "{:08X}#", id.as_raw())?,
+        }
+
+        if self.0.is_remote_frame() {
+            return write!(f, "R");
+        }
+
+        for byte in self.data() {
+            write!(f, "{:02X}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn to_bxcan_frame<F: HalFrame>(frame: &F) -> BxFrame {
+    if frame.is_remote_frame() {
+        BxFrame::new_remote(to_bxcan_id(frame.id()), frame.dlc() as u8)
+    } else {
+        BxFrame::new_data(to_bxcan_id(frame.id()), Data::new(frame.data()).unwrap())
+    }
+}
+
+fn to_bxcan_id(id: Id) -> BxId {
+    match id {
+        Id::Standard(id) => BxId::Standard(StandardId::new(id.as_raw()).unwrap()),
+        Id::Extended(id) => BxId::Extended(ExtendedId::new(id.as_raw()).unwrap()),
+    }
+}
+
+/// ross_protocol packet interface on top of any embedded-hal CAN peripheral, e.g. a frame level `Mock`
+///
+/// `ross_protocol::interface::can::Can` drives the bxcan registers directly, so it cannot be placed
+/// on top of a mock. This interface is a copy of its implementation in ross-protocol 2.15.0, which
+/// newer ross-protocol releases may change without notice. Packets are still segmented and
+/// reassembled by the ross_protocol frame and packet builder code.
+#[derive(Debug)]
+pub struct CanInterface<C: Can> {
+    can: C,
+    packet_builder: Option<PacketBuilder>,
+}
+
+impl<C: Can> CanInterface<C> {
+    pub fn new(can: C) -> Self {
+        Self {
+            can,
+            packet_builder: None,
+        }
+    }
+
+    pub fn into_inner(self) -> C {
+        self.can
+    }
+}
+
+impl<C: Can> Interface for CanInterface<C> {
+    fn try_get_packet(&mut self) -> Result<Packet, InterfaceError> {
+        while let Ok(frame) = self.can.receive() {
+            let ross_frame = Frame::from_bxcan_frame(to_bxcan_frame(&frame))
+                .map_err(InterfaceError::FrameError)?;
+
+            if let Some(ref mut packet_builder) = self.packet_builder {
+                if let Err(err) = packet_builder.add_frame(ross_frame) {
+                    self.packet_builder = None;
+
+                    return Err(InterfaceError::BuilderError(err));
+                }
+            } else {
+                self.packet_builder =
+                    Some(PacketBuilder::new(ross_frame).map_err(InterfaceError::BuilderError)?);
+            }
+
+            if let Some(ref mut packet_builder) = self.packet_builder {
+                if packet_builder.frames_left() == 0 {
+                    let packet = packet_builder
+                        .build()
+                        .map_err(InterfaceError::BuilderError)?;
+
+                    self.packet_builder = None;
+
+                    return Ok(packet);
+                }
+            }
+        }
+
+        Err(InterfaceError::NoPacketReceived)
+    }
+
+    fn try_send_packet(&mut self, packet: &Packet) -> Result<(), InterfaceError> {
+        for frame in packet.to_frames() {
+            let frame = CanFrame(frame.to_bxcan_frame());
+            let frame = C::Frame::new(frame.id(), frame.data()).unwrap();
+
+            if let Ok(Some(_)) = block!(self.can.transmit(&frame)) {
+                return Err(InterfaceError::CanError(CanError::MailboxFull));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Packet of two frames, shared by the CAN and candump tests
+#[cfg(test)]
+pub(crate) fn long_packet() -> Packet {
+    Packet {
+        is_error: false,
+        device_address: 0x2222,
+        data: (0x00..0x0a).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    use ross_protocol::packet::PacketBuilderError;
+
+    use crate::{CanExpectation, Expectation, ExpectationTracker, Times};
+
+    #[test]
+    fn send_segmented_packet_test() {
+        let tracker = ExpectationTracker::new();
+        let mock = ExpectationTracker::mock(tracker.clone());
+
+        let frames = CanFrame::from_packet(&long_packet());
+        assert_eq!(frames.len(), 2);
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::Transmitted(frames[0].clone())),
+        );
+        ExpectationTracker::expect_times(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::TransmitBlocked),
+            Times::Exactly(2),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::Transmitted(frames[1].clone())),
+        );
+
+        let mut interface = CanInterface::new(mock);
+        interface.try_send_packet(&long_packet()).unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn receive_reassembled_packet_test() {
+        let tracker = ExpectationTracker::new();
+        let mock = ExpectationTracker::mock(tracker.clone());
+
+        for frame in CanFrame::from_packet(&long_packet()) {
+            ExpectationTracker::expect(
+                tracker.clone(),
+                &mock,
+                Expectation::Can(CanExpectation::Received(frame)),
+            );
+        }
+
+        let mut interface = CanInterface::new(mock);
+
+        assert_eq!(interface.try_get_packet().unwrap(), long_packet());
+        assert!(matches!(
+            interface.try_get_packet(),
+            Err(InterfaceError::NoPacketReceived)
+        ));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn driver_errors_test() {
+        let tracker = ExpectationTracker::new();
+        let mock = ExpectationTracker::mock(tracker.clone());
+
+        let frames = CanFrame::from_packet(&long_packet());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::MailboxFull(frames[0].clone())),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::Received(frames[1].clone())),
+        );
+
+        let mut interface = CanInterface::new(mock);
+
+        assert!(matches!(
+            interface.try_send_packet(&long_packet()),
+            Err(InterfaceError::CanError(CanError::MailboxFull))
+        ));
+        assert!(matches!(
+            interface.try_get_packet(),
+            Err(InterfaceError::BuilderError(_))
+        ));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn segmentation_round_trip_test() {
+        let packets = [
+            Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![],
+            },
+            Packet {
+                is_error: true,
+                device_address: 0x1111,
+                data: vec![0x01, 0x02],
+            },
+            Packet {
+                is_error: false,
+                device_address: 0x3333,
+                data: (0x00..0x07).collect(),
+            },
+            Packet {
+                is_error: false,
+                device_address: 0x3333,
+                data: (0x00..0x20).collect(),
+            },
+        ];
+
+        for packet in packets.iter() {
+            let tracker = ExpectationTracker::new();
+            let mock = ExpectationTracker::mock(tracker.clone());
+
+            for frame in CanFrame::from_packet(packet) {
+                ExpectationTracker::expect(
+                    tracker.clone(),
+                    &mock,
+                    Expectation::Can(CanExpectation::Transmitted(frame.clone())),
+                );
+            }
+
+            for frame in CanFrame::from_packet(packet) {
+                ExpectationTracker::expect(
+                    tracker.clone(),
+                    &mock,
+                    Expectation::Can(CanExpectation::Received(frame)),
+                );
+            }
+
+            let mut interface = CanInterface::new(mock);
+
+            interface.try_send_packet(packet).unwrap();
+            assert_eq!(&interface.try_get_packet().unwrap(), packet);
+
+            tracker.borrow_mut().done();
+        }
+    }
+
+    #[test]
+    fn builder_reset_test() {
+        let tracker = ExpectationTracker::new();
+        let mock = ExpectationTracker::mock(tracker.clone());
+
+        let interrupting_packet = Packet {
+            is_error: false,
+            device_address: 0x1111,
+            data: vec![0x01],
+        };
+
+        // Like ross_protocol's Can, a frame of another device drops the packet in progress and is lost itself
+        let frames = CanFrame::from_packet(&long_packet())
+            .into_iter()
+            .take(1)
+            .chain(CanFrame::from_packet(&interrupting_packet))
+            .chain(CanFrame::from_packet(&long_packet()));
+
+        for frame in frames {
+            ExpectationTracker::expect(
+                tracker.clone(),
+                &mock,
+                Expectation::Can(CanExpectation::Received(frame)),
+            );
+        }
+
+        let mut interface = CanInterface::new(mock);
+
+        assert!(matches!(
+            interface.try_get_packet(),
+            Err(InterfaceError::BuilderError(
+                PacketBuilderError::DeviceAddressMismatch
+            ))
+        ));
+        assert_eq!(interface.try_get_packet().unwrap(), long_packet());
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn frame_display_test() {
+        assert_eq!(
+            CanFrame::from_packet(&Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x01, 0xab],
+            })[0]
+                .to_string(),
+            "18001111#01AB"
+        );
+        assert_eq!(
+            CanFrame::new_remote(HalStandardId::new(0x123).unwrap(), 0)
+                .unwrap()
+                .to_string(),
+            "123#R"
+        );
+    }
+}
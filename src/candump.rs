@@ -0,0 +1,515 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use bxcan::{Data, ExtendedId, Frame as BxFrame, Id, StandardId};
+use core::fmt::{Display, Formatter, Result as FmtResult};
+
+use ross_protocol::frame::Frame;
+use ross_protocol::interface::InterfaceError;
+use ross_protocol::packet::{Packet, PacketBuilder};
+use ross_protocol::protocol::BROADCAST_ADDRESS;
+
+use crate::{
+    CanFrame, Expectation, ExpectationTracker, InterfaceExpectation, Mock, MockInterfaceError,
+    TrackerHandle,
+};
+
+/// Failure to import a candump log
+#[derive(Debug, Clone, PartialEq)]
+pub enum CandumpError {
+    /// The line with the given number, counted from 1, could not be parsed
+    Syntax(usize, String),
+    /// The frame on the line with the given number could not be assembled into a packet
+    Frame(usize, MockInterfaceError),
+    /// The packet started on the line with the given number is missing frames at the end of the log
+    Incomplete(usize),
+}
+
+impl Display for CandumpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            CandumpError::Syntax(line, message) => write!(f, "line {}: {}", line, message),
+            CandumpError::Frame(line, error) => write!(f, "line {}: {:?}", line, error),
+            CandumpError::Incomplete(line) => {
+                write!(f, "line {}: packet is missing frames", line)
+            }
+        }
+    }
+}
+
+/// Direction of a logged packet as seen by the device under test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PacketDirection {
+    Sent,
+    Received,
+}
+
+impl PacketDirection {
+    /// Classifies packets addressed to the device or broadcast as received and skips all others
+    ///
+    /// Suited for devices that only listen, packets sent by the device need a classifier that
+    /// knows their sender, e.g. from the address carried in the event data.
+    pub fn received_by(device_address: u16) -> impl Fn(&CandumpPacket) -> Option<Self> {
+        move |log_packet| {
+            let address = log_packet.packet.device_address;
+
+            if address == device_address || address == BROADCAST_ADDRESS {
+                Some(PacketDirection::Received)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// A packet together with the time and interface of its last frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandumpPacket {
+    /// Microseconds since the epoch
+    pub timestamp: u64,
+    pub interface: String,
+    pub packet: Packet,
+}
+
+/// Packets of a `candump -l` log, e.g. `(1650000000.000100) can0 10011111#0102`
+///
+/// Interleaved multi-frame packets from different devices are reassembled separately.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CandumpLog {
+    packets: Vec<CandumpPacket>,
+}
+
+impl CandumpLog {
+    pub fn new() -> Self {
+        Self { packets: vec![] }
+    }
+
+    pub fn packets(&self) -> &[CandumpPacket] {
+        &self.packets
+    }
+
+    pub fn push(&mut self, timestamp: u64, interface: &str, packet: Packet) {
+        self.packets.push(CandumpPacket {
+            timestamp,
+            interface: interface.to_string(),
+            packet,
+        });
+    }
+
+    pub fn parse(log: &str) -> Result<Self, CandumpError> {
+        let mut log_packets = Self::new();
+        // Packet builders in progress with their device address and starting line
+        let mut builders: Vec<(u16, usize, PacketBuilder)> = vec![];
+
+        for (i, line) in log.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (timestamp, interface, frame) =
+                parse_line(line).map_err(|message| CandumpError::Syntax(line_number, message))?;
+
+            let frame_error = |error| {
+                CandumpError::Frame(
+                    line_number,
+                    MockInterfaceError::from_interface_error(&error).unwrap(),
+                )
+            };
+
+            let frame = Frame::from_bxcan_frame(frame)
+                .map_err(|error| frame_error(InterfaceError::FrameError(error)))?;
+            let device_address = frame.device_address;
+
+            let position = builders
+                .iter()
+                .position(|(address, _, _)| *address == device_address);
+
+            let position = match position {
+                Some(position) => {
+                    builders[position]
+                        .2
+                        .add_frame(frame)
+                        .map_err(|error| frame_error(InterfaceError::BuilderError(error)))?;
+                    position
+                }
+                None => {
+                    let builder = PacketBuilder::new(frame)
+                        .map_err(|error| frame_error(InterfaceError::BuilderError(error)))?;
+                    builders.push((device_address, line_number, builder));
+                    builders.len() - 1
+                }
+            };
+
+            if builders[position].2.frames_left() == 0 {
+                let (_, _, builder) = builders.remove(position);
+                let packet = builder
+                    .build()
+                    .map_err(|error| frame_error(InterfaceError::BuilderError(error)))?;
+
+                log_packets.push(timestamp, interface, packet);
+            }
+        }
+
+        match builders.first() {
+            Some((_, line_number, _)) => Err(CandumpError::Incomplete(*line_number)),
+            None => Ok(log_packets),
+        }
+    }
+
+    /// Expectations for the device under test, with the direction of every packet given by the classifier
+    ///
+    /// A candump log does not record which node transmitted a frame and `device_address` is the
+    /// address of the receiver, so the direction cannot be derived from the log itself. Packets the
+    /// classifier returns `None` for are skipped.
+    pub fn expectations<F>(&self, direction: F) -> Vec<InterfaceExpectation>
+    where
+        F: Fn(&CandumpPacket) -> Option<PacketDirection>,
+    {
+        self.packets
+            .iter()
+            .filter_map(|log_packet| {
+                let packet = log_packet.packet.clone();
+
+                match direction(log_packet)? {
+                    PacketDirection::Sent => Some(InterfaceExpectation::SentPacket(packet)),
+                    PacketDirection::Received => Some(InterfaceExpectation::ReceivedPacket(packet)),
+                }
+            })
+            .collect()
+    }
+
+    /// Expects the logged traffic on the given mock, see `expectations`
+    pub fn load<H, F>(&self, tracker: H, mock: &Mock<H>, direction: F)
+    where
+        H: TrackerHandle,
+        F: Fn(&CandumpPacket) -> Option<PacketDirection>,
+    {
+        for expectation in self.expectations(direction) {
+            ExpectationTracker::expect(tracker.clone(), mock, Expectation::Interface(expectation));
+        }
+    }
+
+    /// Logs the packets of a session, one millisecond apart
+    ///
+    /// Errors and matchers carry no packet and are skipped, typed events are logged as their packets.
+    pub fn from_expectations(interface: &str, expectations: &[InterfaceExpectation]) -> Self {
+        let mut log = Self::new();
+
+        for expectation in expectations.iter() {
+            let packet = match expectation {
+                InterfaceExpectation::SentPacket(packet) => packet,
+                InterfaceExpectation::ReceivedPacket(packet) => packet,
+                InterfaceExpectation::SentEvent(event) => event.packet(),
+                InterfaceExpectation::ReceivedEvent(event) => event.packet(),
+                _ => continue,
+            };
+
+            log.push(log.packets.len() as u64 * 1000, interface, packet.clone());
+        }
+
+        log
+    }
+}
+
+impl Display for CandumpLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for log_packet in self.packets.iter() {
+            for frame in CanFrame::from_packet(&log_packet.packet) {
+                writeln!(
+                    f,
+                    "({}.{:06}) {} {}",
+                    log_packet.timestamp / 1_000_000,
+                    log_packet.timestamp % 1_000_000,
+                    log_packet.interface,
+                    frame
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_line(line: &str) -> Result<(u64, &str, BxFrame), String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    let (timestamp, interface, frame) = match tokens.as_slice() {
+        [timestamp, interface, frame, ..] => (*timestamp, *interface, *frame),
+        _ => return Err("expected a timestamp, an interface and a frame".to_string()),
+    };
+
+    let timestamp = timestamp
+        .strip_prefix('(')
+        .and_then(|timestamp| timestamp.strip_suffix(')'))
+        .and_then(parse_timestamp)
+        .ok_or_else(|| format!("invalid timestamp {}", timestamp))?;
+
+    let (id, data) = frame
+        .split_once('#')
+        .ok_or_else(|| format!("invalid frame {}", frame))?;
+
+    if !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid frame id {}", id));
+    }
+
+    let id = match id.len() {
+        3 => u16::from_str_radix(id, 16)
+            .ok()
+            .and_then(StandardId::new)
+            .map(Id::Standard),
+        8 => u32::from_str_radix(id, 16)
+            .ok()
+            .and_then(ExtendedId::new)
+            .map(Id::Extended),
+        _ => None,
+    }
+    .ok_or_else(|| format!("invalid frame id {}", id))?;
+
+    if let Some(dlc) = data.strip_prefix('R') {
+        let dlc = match dlc {
+            "" => Some(0),
+            dlc => dlc.parse::<u8>().ok().filter(|dlc| *dlc <= 8),
+        }
+        .ok_or_else(|| format!("invalid remote frame length {}", dlc))?;
+
+        return Ok((timestamp, interface, BxFrame::new_remote(id, dlc)));
+    }
+
+    let bytes = (0..data.len())
+        .step_by(2)
+        .map(|i| {
+            data.get(i..i + 2)
+                .filter(|byte| byte.chars().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| format!("invalid frame data {}", data))?;
+
+    let data = Data::new(&bytes).ok_or_else(|| format!("frame data {} is too long", data))?;
+
+    Ok((timestamp, interface, BxFrame::new_data(id, data)))
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let (seconds, fraction) = timestamp.split_once('.')?;
+
+    let is_digits = |text: &str| text.chars().all(|c| c.is_ascii_digit());
+
+    if fraction.len() != 6 || !is_digits(seconds) || !is_digits(fraction) {
+        return None;
+    }
+
+    seconds
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(1_000_000)?
+        .checked_add(fraction.parse::<u64>().ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ross_protocol::convert_packet::ConvertPacket;
+    use ross_protocol::event::button::ButtonPressedEvent;
+    use ross_protocol::event::relay::{RelaySetValueEvent, RelayValue};
+    use ross_protocol::interface::Interface;
+
+    use crate::can::long_packet;
+
+    #[test]
+    fn export_import_round_trip_test() {
+        let expectations = vec![
+            InterfaceExpectation::ReceivedPacket(Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x01, 0x02],
+            }),
+            InterfaceExpectation::ReceiveError(MockInterfaceError::NoPacketReceived),
+            InterfaceExpectation::SentPacket(long_packet()),
+        ];
+
+        let log = CandumpLog::from_expectations("can0", &expectations).to_string();
+
+        assert_eq!(
+            log,
+            "(0.000000) can0 18001111#0102\n\
+             (0.001000) can0 1C002222#0100010203040506\n\
+             (0.001000) can0 14002222#01070809\n"
+        );
+
+        let imported = CandumpLog::parse(&log).unwrap();
+
+        assert_eq!(imported.packets()[1].timestamp, 1000);
+        assert_eq!(imported.packets()[1].interface, "can0");
+        // The device 0x1111 receives packets addressed to it and sends all others
+        assert_eq!(
+            imported.expectations(|log_packet| {
+                PacketDirection::received_by(0x1111)(log_packet).or(Some(PacketDirection::Sent))
+            }),
+            vec![expectations[0].clone(), expectations[2].clone()]
+        );
+    }
+
+    #[test]
+    fn interleaved_frames_test() {
+        let log = "\
+            (1650000000.000100) can0 1C002222#0100010203040506
+            (1650000000.000200) can0 18001111#AA
+            (1650000000.000300) can0 14002222#01070809
+        ";
+
+        let imported = CandumpLog::parse(log).unwrap();
+
+        assert_eq!(imported.packets().len(), 2);
+        assert_eq!(imported.packets()[0].packet.data, vec![0xaa]);
+        assert_eq!(imported.packets()[1].packet, long_packet());
+        assert_eq!(imported.packets()[1].timestamp, 1_650_000_000_000_300);
+    }
+
+    #[test]
+    fn import_errors_test() {
+        assert_eq!(
+            CandumpLog::parse("(0.000000) can0 123#01"),
+            Err(CandumpError::Frame(1, MockInterfaceError::FrameIsStandard))
+        );
+        assert_eq!(
+            CandumpLog::parse("(0.000000) can0 18001111#R"),
+            Err(CandumpError::Frame(1, MockInterfaceError::FrameIsRemote))
+        );
+        assert_eq!(
+            CandumpLog::parse("\n(0.000000) can0 1C002222#0100010203040506"),
+            Err(CandumpError::Incomplete(2))
+        );
+        assert_eq!(
+            CandumpLog::parse("(0.000000) can0 18001111#012"),
+            Err(CandumpError::Syntax(
+                1,
+                "invalid frame data 012".to_string()
+            ))
+        );
+        assert_eq!(
+            CandumpLog::parse("(0.000000) can0 18001111#+1"),
+            Err(CandumpError::Syntax(1, "invalid frame data +1".to_string()))
+        );
+        assert_eq!(
+            CandumpLog::parse("(99999999999999.000000) can0 18001111#01"),
+            Err(CandumpError::Syntax(
+                1,
+                "invalid timestamp (99999999999999.000000)".to_string()
+            ))
+        );
+        assert_eq!(
+            CandumpLog::parse("(0.000000) can0 +23#01"),
+            Err(CandumpError::Syntax(1, "invalid frame id +23".to_string()))
+        );
+    }
+
+    const CONTROLLER_ADDRESS: u16 = 0x0001;
+    const BUTTON_ADDRESS: u16 = 0x0002;
+    const RELAY_ADDRESS: u16 = 0x0003;
+
+    fn button_pressed() -> ButtonPressedEvent {
+        ButtonPressedEvent {
+            receiver_address: CONTROLLER_ADDRESS,
+            button_address: BUTTON_ADDRESS,
+            index: 0,
+        }
+    }
+
+    fn relay_set_value() -> RelaySetValueEvent {
+        RelaySetValueEvent {
+            relay_address: RELAY_ADDRESS,
+            transmitter_address: CONTROLLER_ADDRESS,
+            index: 0,
+            value: RelayValue::Single(true),
+        }
+    }
+
+    /// Controller side classifier, it sends relay events and receives everything addressed to it
+    fn controller_direction(log_packet: &CandumpPacket) -> Option<PacketDirection> {
+        match RelaySetValueEvent::try_from_packet(&log_packet.packet) {
+            Ok(event) if event.transmitter_address == CONTROLLER_ADDRESS => {
+                Some(PacketDirection::Sent)
+            }
+            _ => PacketDirection::received_by(CONTROLLER_ADDRESS)(log_packet),
+        }
+    }
+
+    #[test]
+    fn event_direction_test() {
+        let log = CandumpLog::from_expectations(
+            "can0",
+            &[
+                InterfaceExpectation::ReceivedPacket(button_pressed().to_packet()),
+                InterfaceExpectation::SentPacket(relay_set_value().to_packet()),
+            ],
+        )
+        .to_string();
+        let imported = CandumpLog::parse(&log).unwrap();
+
+        assert_eq!(
+            imported.expectations(controller_direction),
+            vec![
+                InterfaceExpectation::ReceivedPacket(button_pressed().to_packet()),
+                InterfaceExpectation::SentPacket(relay_set_value().to_packet()),
+            ]
+        );
+        assert_eq!(
+            imported.expectations(PacketDirection::received_by(RELAY_ADDRESS)),
+            vec![InterfaceExpectation::ReceivedPacket(
+                relay_set_value().to_packet()
+            )]
+        );
+        assert_eq!(
+            imported.expectations(PacketDirection::received_by(BUTTON_ADDRESS)),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn broadcast_direction_test() {
+        let mut log = CandumpLog::new();
+        log.push(
+            0,
+            "can0",
+            Packet {
+                is_error: false,
+                device_address: BROADCAST_ADDRESS,
+                data: vec![0x01],
+            },
+        );
+
+        assert_eq!(
+            log.expectations(PacketDirection::received_by(BUTTON_ADDRESS))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn load_test() {
+        let tracker = ExpectationTracker::new();
+        let mut controller = ExpectationTracker::mock(tracker.clone());
+
+        let mut log = CandumpLog::new();
+        log.push(0, "can0", button_pressed().to_packet());
+        log.push(100, "can0", relay_set_value().to_packet());
+        log.load(tracker.clone(), &controller, controller_direction);
+
+        let packet = controller.try_get_packet().unwrap();
+        assert_eq!(
+            ButtonPressedEvent::try_from_packet(&packet).unwrap(),
+            button_pressed()
+        );
+        controller
+            .try_send_packet(&relay_set_value().to_packet())
+            .unwrap();
+
+        tracker.borrow_mut().done();
+    }
+}
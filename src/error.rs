@@ -0,0 +1,123 @@
+use ross_protocol::frame::FrameError;
+use ross_protocol::interface::can::CanError;
+use ross_protocol::interface::usart::UsartError;
+use ross_protocol::interface::InterfaceError;
+use ross_protocol::packet::PacketBuilderError;
+
+/// Interface error injected by the mock
+///
+/// `InterfaceError` can be neither cloned nor compared, so expectations use this mirror of it instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MockInterfaceError {
+    NoPacketReceived,
+    CanBufferOverrun,
+    CanMailboxFull,
+    UsartReadError,
+    BuilderOutOfOrder,
+    BuilderSingleFramePacket,
+    BuilderTooManyFrames,
+    BuilderWrongFrameType,
+    BuilderDeviceAddressMismatch,
+    BuilderMissingFrames,
+    FrameIsStandard,
+    FrameIsRemote,
+    FrameIdMissing,
+    FrameWrongSize,
+    FrameCobsError,
+}
+
+impl From<MockInterfaceError> for InterfaceError {
+    fn from(error: MockInterfaceError) -> Self {
+        match error {
+            MockInterfaceError::NoPacketReceived => InterfaceError::NoPacketReceived,
+            MockInterfaceError::CanBufferOverrun => {
+                InterfaceError::CanError(CanError::BufferOverrun)
+            }
+            MockInterfaceError::CanMailboxFull => InterfaceError::CanError(CanError::MailboxFull),
+            MockInterfaceError::UsartReadError => InterfaceError::UsartError(UsartError::ReadError),
+            MockInterfaceError::BuilderOutOfOrder => {
+                InterfaceError::BuilderError(PacketBuilderError::OutOfOrder)
+            }
+            MockInterfaceError::BuilderSingleFramePacket => {
+                InterfaceError::BuilderError(PacketBuilderError::SingleFramePacket)
+            }
+            MockInterfaceError::BuilderTooManyFrames => {
+                InterfaceError::BuilderError(PacketBuilderError::TooManyFrames)
+            }
+            MockInterfaceError::BuilderWrongFrameType => {
+                InterfaceError::BuilderError(PacketBuilderError::WrongFrameType)
+            }
+            MockInterfaceError::BuilderDeviceAddressMismatch => {
+                InterfaceError::BuilderError(PacketBuilderError::DeviceAddressMismatch)
+            }
+            MockInterfaceError::BuilderMissingFrames => {
+                InterfaceError::BuilderError(PacketBuilderError::MissingFrames)
+            }
+            MockInterfaceError::FrameIsStandard => {
+                InterfaceError::FrameError(FrameError::FrameIsStandard)
+            }
+            MockInterfaceError::FrameIsRemote => {
+                InterfaceError::FrameError(FrameError::FrameIsRemote)
+            }
+            MockInterfaceError::FrameIdMissing => {
+                InterfaceError::FrameError(FrameError::FrameIdMissing)
+            }
+            MockInterfaceError::FrameWrongSize => InterfaceError::FrameError(FrameError::WrongSize),
+            MockInterfaceError::FrameCobsError => InterfaceError::FrameError(FrameError::CobsError),
+        }
+    }
+}
+
+impl MockInterfaceError {
+    /// Mirrors an interface error, `None` if it has no counterpart
+    #[allow(unreachable_patterns)]
+    pub fn from_interface_error(error: &InterfaceError) -> Option<Self> {
+        match error {
+            InterfaceError::NoPacketReceived => Some(MockInterfaceError::NoPacketReceived),
+            InterfaceError::CanError(CanError::BufferOverrun) => {
+                Some(MockInterfaceError::CanBufferOverrun)
+            }
+            InterfaceError::CanError(CanError::MailboxFull) => {
+                Some(MockInterfaceError::CanMailboxFull)
+            }
+            InterfaceError::UsartError(UsartError::ReadError) => {
+                Some(MockInterfaceError::UsartReadError)
+            }
+            InterfaceError::BuilderError(PacketBuilderError::OutOfOrder) => {
+                Some(MockInterfaceError::BuilderOutOfOrder)
+            }
+            InterfaceError::BuilderError(PacketBuilderError::SingleFramePacket) => {
+                Some(MockInterfaceError::BuilderSingleFramePacket)
+            }
+            InterfaceError::BuilderError(PacketBuilderError::TooManyFrames) => {
+                Some(MockInterfaceError::BuilderTooManyFrames)
+            }
+            InterfaceError::BuilderError(PacketBuilderError::WrongFrameType) => {
+                Some(MockInterfaceError::BuilderWrongFrameType)
+            }
+            InterfaceError::BuilderError(PacketBuilderError::DeviceAddressMismatch) => {
+                Some(MockInterfaceError::BuilderDeviceAddressMismatch)
+            }
+            InterfaceError::BuilderError(PacketBuilderError::MissingFrames) => {
+                Some(MockInterfaceError::BuilderMissingFrames)
+            }
+            InterfaceError::FrameError(FrameError::FrameIsStandard) => {
+                Some(MockInterfaceError::FrameIsStandard)
+            }
+            InterfaceError::FrameError(FrameError::FrameIsRemote) => {
+                Some(MockInterfaceError::FrameIsRemote)
+            }
+            InterfaceError::FrameError(FrameError::FrameIdMissing) => {
+                Some(MockInterfaceError::FrameIdMissing)
+            }
+            InterfaceError::FrameError(FrameError::WrongSize) => {
+                Some(MockInterfaceError::FrameWrongSize)
+            }
+            InterfaceError::FrameError(FrameError::CobsError) => {
+                Some(MockInterfaceError::FrameCobsError)
+            }
+            // ross_protocol's std feature adds a serial error without a counterpart
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
+
+use ross_protocol::convert_packet::{ConvertPacket, ConvertPacketError};
+use ross_protocol::packet::Packet;
+
+use crate::InterfaceExpectation;
+
+/// Typed ross_protocol event, stored as its packet together with a decoder for diagnostics
+#[derive(Clone)]
+pub struct EventPacket {
+    packet: Packet,
+    decode: fn(&Packet) -> Result<String, ConvertPacketError>,
+}
+
+fn decode_event<E: ConvertPacket<E> + Debug>(
+    packet: &Packet,
+) -> Result<String, ConvertPacketError> {
+    E::try_from_packet(packet).map(|event| format!("{:?}", event))
+}
+
+impl EventPacket {
+    pub fn new<E: ConvertPacket<E> + Debug>(event: &E) -> Self {
+        Self {
+            packet: event.to_packet(),
+            decode: decode_event::<E>,
+        }
+    }
+
+    pub fn packet(&self) -> &Packet {
+        &self.packet
+    }
+
+    /// Decodes any packet as the same event type as this one
+    pub fn decode(&self, packet: &Packet) -> Result<String, ConvertPacketError> {
+        (self.decode)(packet)
+    }
+
+    /// Writes the packet decoded as this event type, falling back to its raw fields
+    pub(crate) fn fmt_decoded(&self, f: &mut Formatter<'_>, packet: &Packet) -> FmtResult {
+        match self.decode(packet) {
+            Ok(event) => write!(f, "{}", event),
+            Err(err) => write!(
+                f,
+                "{:?} (not decodable as the expected event: {:?})",
+                packet, err
+            ),
+        }
+    }
+}
+
+impl Debug for EventPacket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.fmt_decoded(f, &self.packet)
+    }
+}
+
+impl Display for EventPacket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.fmt_decoded(f, &self.packet)
+    }
+}
+
+impl PartialEq for EventPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.packet == other.packet
+    }
+}
+
+impl InterfaceExpectation {
+    pub fn sent_event<E: ConvertPacket<E> + Debug>(event: &E) -> Self {
+        InterfaceExpectation::SentEvent(EventPacket::new(event))
+    }
+
+    pub fn received_event<E: ConvertPacket<E> + Debug>(event: &E) -> Self {
+        InterfaceExpectation::ReceivedEvent(EventPacket::new(event))
+    }
+}
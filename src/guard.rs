@@ -0,0 +1,34 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use crate::{ExpectationTracker, TrackerHandle};
+
+/// Verifies the tracker with `done` when dropped, unless the thread is already panicking
+#[must_use = "the tracker is verified when the guard is dropped"]
+#[derive(Debug)]
+pub struct ExpectationGuard<H: TrackerHandle = Rc<RefCell<ExpectationTracker>>> {
+    expectation_tracker: H,
+    armed: bool,
+}
+
+impl<H: TrackerHandle> ExpectationGuard<H> {
+    pub(crate) fn new(expectation_tracker: H) -> Self {
+        Self {
+            expectation_tracker,
+            armed: true,
+        }
+    }
+
+    /// Drops the guard without verifying, for tests that intentionally stop early
+    pub fn dismiss(mut self) {
+        self.armed = false;
+    }
+}
+
+impl<H: TrackerHandle> Drop for ExpectationGuard<H> {
+    fn drop(&mut self) {
+        if self.armed && !std::thread::panicking() {
+            self.expectation_tracker.with(|tracker| tracker.done());
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+#[cfg(feature = "sync")]
+use std::sync::{Arc, Mutex};
+
+use crate::ExpectationTracker;
+
+/// Shared reference to an expectation tracker, through which mocks verify their calls
+pub trait TrackerHandle: Clone {
+    fn with<R>(&self, f: impl FnOnce(&mut ExpectationTracker) -> R) -> R;
+}
+
+impl TrackerHandle for Rc<RefCell<ExpectationTracker>> {
+    fn with<R>(&self, f: impl FnOnce(&mut ExpectationTracker) -> R) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+/// Tracker that can be shared between threads
+#[cfg(feature = "sync")]
+pub type SyncTracker = Arc<Mutex<ExpectationTracker>>;
+
+#[cfg(feature = "sync")]
+impl TrackerHandle for SyncTracker {
+    fn with<R>(&self, f: impl FnOnce(&mut ExpectationTracker) -> R) -> R {
+        // A mismatch panicking on another thread must not hide the tracker from `done`
+        let mut tracker = self.lock().unwrap_or_else(|err| err.into_inner());
+        f(&mut tracker)
+    }
+}
+
+/// Mock that can be moved to other threads
+#[cfg(feature = "sync")]
+pub type SyncMock = crate::Mock<SyncTracker>;
@@ -1,29 +1,99 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 extern crate alloc;
 
 use alloc::rc::Rc;
+use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::fmt::Debug;
+use core::time::Duration;
+#[cfg(feature = "sync")]
+use std::sync::{Arc, Mutex};
 
+use embedded_hal::can::ErrorKind;
+use embedded_hal::digital::v2::PinState;
 use ross_protocol::packet::Packet;
 
+mod builder;
+pub use builder::*;
+
+mod bus;
+pub use bus::*;
+
+mod can;
+pub use can::*;
+
+mod candump;
+pub use candump::*;
+
+mod error;
+pub use error::*;
+
+mod event;
+pub use event::*;
+
+mod handle;
+pub use handle::*;
+
+mod matcher;
+pub use matcher::*;
+
 mod mock;
 pub use mock::*;
 
+mod recorder;
+pub use recorder::*;
+
+mod report;
+pub use report::*;
+
+mod scenario;
+
+mod stimulus;
+pub use stimulus::*;
+
+mod transcript;
+pub use transcript::*;
+
+mod waveform;
+pub use waveform::*;
+
+#[cfg(feature = "std")]
+mod guard;
+#[cfg(feature = "std")]
+pub use guard::*;
+
+// Used by the exported macros, so callers do not need these crates under the same names
+#[doc(hidden)]
+pub use embedded_hal;
+#[doc(hidden)]
+pub use ross_protocol;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expectation {
     Interface(InterfaceExpectation),
     InputPin(InputPinExpectation),
     OutputPin(OutputPinExpectation),
+    Can(CanExpectation),
+    Delay(DelayExpectation),
+    Timer(TimerExpectation),
+    Pwm(PwmExpectation),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum InterfaceExpectation {
     SentPacket(Packet),
     ReceivedPacket(Packet),
+    /// Sent packet satisfying all of the matchers
+    SentPacketMatching(Vec<PacketMatcher>),
+    SentEvent(EventPacket),
+    ReceivedEvent(EventPacket),
+    /// `try_send_packet` fails with the given error, regardless of the packet
+    SendError(MockInterfaceError),
+    /// `try_get_packet` fails with the given error
+    ReceiveError(MockInterfaceError),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -36,74 +106,606 @@ pub enum InputPinExpectation {
 pub enum OutputPinExpectation {
     SetHigh,
     SetLow,
+    IsSetHigh,
+    IsSetLow,
+    Toggle,
+    /// Toggle that is expected to leave the pin at the given level
+    ToggleTo(PinState),
 }
 
-#[derive(Debug)]
-pub struct ExpectationTracker {
-    expectations: Rc<RefCell<(usize, Vec<(usize, Expectation)>)>>,
+/// Frame level expectation for the embedded-hal CAN peripheral implementation
+#[derive(Debug, Clone, PartialEq)]
+pub enum CanExpectation {
+    Transmitted(CanFrame),
+    /// `receive` returns the given frame, a `receive` without pending frames returns `WouldBlock`
+    Received(CanFrame),
+    /// `transmit` finds all mailboxes full and replaces the given lower priority pending frame
+    MailboxFull(CanFrame),
+    /// `transmit` finds all mailboxes full of higher priority frames and returns `WouldBlock`
+    TransmitBlocked,
+    /// `transmit` fails with the given error, regardless of the frame
+    TransmitError(ErrorKind),
+    /// `receive` fails with the given error
+    ReceiveError(ErrorKind),
+}
+
+/// Expected duration of a `delay_ms` or `delay_us` call
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DelayExpectation {
+    Exactly(Duration),
+    AtLeast(Duration),
+    AtMost(Duration),
+    /// Duration within the inclusive bounds
+    Between(Duration, Duration),
+}
+
+impl DelayExpectation {
+    pub fn contains(&self, duration: Duration) -> bool {
+        match *self {
+            DelayExpectation::Exactly(expected) => duration == expected,
+            DelayExpectation::AtLeast(min) => duration >= min,
+            DelayExpectation::AtMost(max) => duration <= max,
+            DelayExpectation::Between(min, max) => duration >= min && duration <= max,
+        }
+    }
+}
+
+/// Expectation for the embedded-hal `CountDown` timer implementation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimerExpectation {
+    /// `start` with the given period
+    Started(Duration),
+    /// `wait` returns as if the timer expired, regardless of the virtual clock
+    Expired,
+}
+
+/// Expectation for the embedded-hal `PwmPin` implementation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PwmExpectation {
+    Enable,
+    Disable,
+    SetDuty(u16),
+    /// `set_duty` with a duty cycle within the inclusive bounds
+    SetDutyBetween(u16, u16),
+    /// `get_duty` returns the given duty cycle
+    GetDuty(u16),
+    /// `get_max_duty` returns the given duty cycle, which is kept for later unexpected reads
+    GetMaxDuty(u16),
+}
+
+/// Order in which mock calls are matched against expectations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExpectationOrder {
+    /// Every call must verify the next expectation, in the order they were added across all mocks
+    Strict,
+    /// Every call must verify the next pending expectation of the calling mock, ignoring other mocks
+    ///
+    /// This is the order of trackers created with `ExpectationTracker::new`.
+    PerMock,
+    /// Every call verifies the first compatible pending expectation of the calling mock
+    Unordered,
+}
+
+/// Number of calls a single expectation has to verify
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Times {
+    Exactly(usize),
+    AtLeast(usize),
+    AtMost(usize),
+    Any,
+}
+
+impl Times {
+    pub fn min(&self) -> usize {
+        match *self {
+            Times::Exactly(times) => times,
+            Times::AtLeast(times) => times,
+            Times::AtMost(_) => 0,
+            Times::Any => 0,
+        }
+    }
+
+    pub fn max(&self) -> Option<usize> {
+        match *self {
+            Times::Exactly(times) => Some(times),
+            Times::AtLeast(_) => None,
+            Times::AtMost(times) => Some(times),
+            Times::Any => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ExpectationEntry {
     mock_index: usize,
+    expectation: Expectation,
+    times: Times,
+    calls: usize,
+}
+
+impl ExpectationEntry {
+    fn is_satisfied(&self) -> bool {
+        self.calls >= self.times.min()
+    }
+
+    fn is_saturated(&self) -> bool {
+        match self.times.max() {
+            Some(max) => self.calls >= max,
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MockState {
+    name: Option<String>,
+    output_state: PinState,
+    /// Input waveform with the time it was started at
+    waveform: Option<(Duration, Waveform)>,
+    /// Running timer with its next deadline and period
+    timer: Option<(Duration, Duration)>,
+    /// Every duty cycle set on the PWM pin, in order
+    duty_history: Vec<u16>,
+    max_duty: u16,
+}
+
+/// Expectations and mock state shared by all mocks through a `TrackerHandle`
+///
+/// Cloning the tracker itself copies its state, mocks stay attached to the original handle.
+#[derive(Debug, Clone)]
+pub struct ExpectationTracker {
+    expectations: Vec<ExpectationEntry>,
+    cursor: usize,
+    order: ExpectationOrder,
+    mocks: Vec<MockState>,
+    call_count: usize,
+    mismatches: Vec<Mismatch>,
+    panic_on_mismatch: bool,
+    clock: Duration,
 }
 
 impl ExpectationTracker {
     pub fn new() -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Self {
-            expectations: Rc::new(RefCell::new((0, vec![]))),
-            mock_index: 0,
-        }))
+        Self::with_order(ExpectationOrder::PerMock)
     }
 
-    pub fn expect(tracker: Rc<RefCell<ExpectationTracker>>, mock: &Mock, expectation: Expectation) {
-        tracker
-            .borrow_mut()
-            .expectations
-            .borrow_mut()
-            .1
-            .push((mock.get_index(), expectation));
+    /// Creates a tracker together with a guard that verifies it when dropped
+    #[cfg(feature = "std")]
+    #[must_use = "the tracker is verified when the guard is dropped"]
+    pub fn new_guarded() -> (Rc<RefCell<Self>>, ExpectationGuard) {
+        let tracker = Self::new();
+        let guard = Self::guard(tracker.clone());
+
+        (tracker, guard)
     }
 
-    pub fn mock(tracker: Rc<RefCell<ExpectationTracker>>) -> Mock {
-        tracker.borrow_mut().mock_index += 1;
-        Mock::new(tracker.clone(), tracker.borrow().mock_index - 1)
+    /// Creates a guard that verifies the tracker when dropped
+    #[cfg(feature = "std")]
+    #[must_use = "the tracker is verified when the guard is dropped"]
+    pub fn guard<H: TrackerHandle>(tracker: H) -> ExpectationGuard<H> {
+        ExpectationGuard::new(tracker)
     }
 
-    pub fn done(&mut self) {
-        assert_eq!(
-            self.expectations.borrow().0,
-            self.expectations.borrow().1.len()
-        );
+    pub fn with_order(order: ExpectationOrder) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self::create(order)))
+    }
+
+    /// Creates a tracker whose mocks can be moved to other threads
+    #[cfg(feature = "sync")]
+    pub fn new_sync() -> SyncTracker {
+        Self::sync_with_order(ExpectationOrder::PerMock)
     }
-}
 
-impl Clone for ExpectationTracker {
-    fn clone(&self) -> Self {
+    #[cfg(feature = "sync")]
+    pub fn sync_with_order(order: ExpectationOrder) -> SyncTracker {
+        Arc::new(Mutex::new(Self::create(order)))
+    }
+
+    fn create(order: ExpectationOrder) -> Self {
         Self {
-            expectations: self.expectations.clone(),
-            mock_index: self.mock_index,
+            expectations: vec![],
+            cursor: 0,
+            order,
+            mocks: vec![],
+            call_count: 0,
+            mismatches: vec![],
+            panic_on_mismatch: true,
+            clock: Duration::ZERO,
+        }
+    }
+
+    pub fn expect<H: TrackerHandle>(tracker: H, mock: &Mock<H>, expectation: Expectation) {
+        Self::expect_times(tracker, mock, expectation, Times::Exactly(1));
+    }
+
+    /// Expects a call to be repeated, repeated expectations are matched greedily
+    pub fn expect_times<H: TrackerHandle>(
+        tracker: H,
+        mock: &Mock<H>,
+        expectation: Expectation,
+        times: Times,
+    ) {
+        tracker.with(|tracker| tracker.push_expectation(mock.get_index(), expectation, times));
+    }
+
+    pub fn mock<H: TrackerHandle>(tracker: H) -> Mock<H> {
+        Self::mock_with_state(tracker, PinState::Low)
+    }
+
+    /// Creates a mock whose output pin starts at the given level
+    pub fn mock_with_state<H: TrackerHandle>(tracker: H, output_state: PinState) -> Mock<H> {
+        Self::add_mock(tracker, None, output_state)
+    }
+
+    /// Creates a mock that is referred to by name in diagnostics
+    pub fn named_mock<H: TrackerHandle>(tracker: H, name: &str) -> Mock<H> {
+        Self::add_mock(tracker, Some(name.to_string()), PinState::Low)
+    }
+
+    fn add_mock<H: TrackerHandle>(
+        tracker: H,
+        name: Option<String>,
+        output_state: PinState,
+    ) -> Mock<H> {
+        let index = tracker.with(|tracker| {
+            tracker.mocks.push(MockState {
+                name,
+                output_state,
+                waveform: None,
+                timer: None,
+                duty_history: vec![],
+                max_duty: u16::MAX,
+            });
+            tracker.mocks.len() - 1
+        });
+
+        Mock::new(tracker, index)
+    }
+
+    /// Returns a handle to the mock with the given name
+    pub fn find_mock<H: TrackerHandle>(tracker: H, name: &str) -> Option<Mock<H>> {
+        let index = tracker.with(|tracker| {
+            tracker
+                .mocks
+                .iter()
+                .position(|mock| mock.name.as_deref() == Some(name))
+        })?;
+
+        Some(Mock::new(tracker, index))
+    }
+
+    pub fn order(&self) -> ExpectationOrder {
+        self.order
+    }
+
+    /// Virtual time passed since the tracker was created, advanced by delay calls
+    pub fn now(&self) -> Duration {
+        self.clock
+    }
+
+    /// Advances the virtual clock without a delay call, e.g. to simulate time spent computing
+    pub fn advance(&mut self, duration: Duration) {
+        self.clock += duration;
+    }
+
+    /// Records mismatching calls instead of panicking, see `verify`
+    pub fn set_panic_on_mismatch(&mut self, panic_on_mismatch: bool) {
+        self.panic_on_mismatch = panic_on_mismatch;
+    }
+
+    pub fn mismatches(&self) -> &[Mismatch] {
+        &self.mismatches
+    }
+
+    /// Collects all recorded mismatches and unsatisfied expectations
+    pub fn verify(&self) -> Result<(), VerificationReport> {
+        let pending: Vec<PendingExpectation> = self
+            .expectations
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !entry.is_satisfied())
+            .map(|(position, entry)| PendingExpectation {
+                position,
+                mock: self.mock_id(entry.mock_index),
+                expectation: entry.expectation.clone(),
+                times: entry.times,
+                calls: entry.calls,
+            })
+            .collect();
+
+        if self.mismatches.is_empty() && pending.is_empty() {
+            Ok(())
+        } else {
+            Err(VerificationReport {
+                mismatches: self.mismatches.clone(),
+                pending,
+            })
+        }
+    }
+
+    pub fn done(&mut self) {
+        if let Err(report) = self.verify() {
+            panic!("{}", report);
         }
     }
-}
 
-impl Iterator for ExpectationTracker {
-    type Item = (usize, Expectation);
+    /// Adds an expectation, returning its position
+    pub(crate) fn push_expectation(
+        &mut self,
+        mock_index: usize,
+        expectation: Expectation,
+        times: Times,
+    ) -> usize {
+        self.expectations.push(ExpectationEntry {
+            mock_index,
+            expectation,
+            times,
+            calls: 0,
+        });
+
+        self.expectations.len() - 1
+    }
+
+    pub(crate) fn set_times(&mut self, position: usize, times: Times) {
+        self.expectations[position].times = times;
+    }
+
+    pub(crate) fn mock_id(&self, mock_index: usize) -> MockId {
+        MockId {
+            index: mock_index,
+            name: self.mocks[mock_index].name.clone(),
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        (*self.expectations).borrow_mut().0 += 1;
+    /// Iterates over all expectations with the index of their mock, in the order they were added
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (usize, &Expectation, Times)> {
         self.expectations
-            .borrow()
-            .1
-            .get(self.expectations.borrow().0 - 1)
-            .cloned()
+            .iter()
+            .map(|entry| (entry.mock_index, &entry.expectation, entry.times))
+    }
+
+    pub(crate) fn output_state(&self, mock_index: usize) -> PinState {
+        self.mocks[mock_index].output_state
+    }
+
+    pub(crate) fn set_waveform(&mut self, mock_index: usize, waveform: Option<Waveform>) {
+        self.mocks[mock_index].waveform = waveform.map(|waveform| (self.clock, waveform));
+    }
+
+    /// Input level of a mock driven by a waveform
+    pub(crate) fn input_state(&self, mock_index: usize) -> Option<PinState> {
+        let (start, waveform) = self.mocks[mock_index].waveform.as_ref()?;
+
+        Some(waveform.level_at(self.clock - *start))
+    }
+
+    /// Starts the timer of a mock, it expires one period from now
+    pub(crate) fn start_timer(&mut self, mock_index: usize, period: Duration) {
+        self.mocks[mock_index].timer = Some((self.clock + period, period));
+    }
+
+    /// Expires the timer of a mock ahead of its deadline, the next period starts now
+    pub(crate) fn expire_timer(&mut self, mock_index: usize) {
+        let clock = self.clock;
+
+        if let Some((deadline, period)) = self.mocks[mock_index].timer.as_mut() {
+            *deadline = clock + *period;
+        }
+    }
+
+    /// Checks if the timer of a mock has reached its deadline, returning `None` if it was never started
+    ///
+    /// Like the single update flag of a hardware timer, an expiry after several missed periods is
+    /// reported once and the deadline moves to the first period boundary after the current time.
+    pub(crate) fn poll_timer(&mut self, mock_index: usize) -> Option<bool> {
+        let clock = self.clock;
+        let (deadline, period) = self.mocks[mock_index].timer.as_mut()?;
+
+        if *deadline > clock {
+            return Some(false);
+        }
+
+        if period.is_zero() {
+            *deadline = clock;
+        } else {
+            let periods = (clock - *deadline).as_nanos() / period.as_nanos() + 1;
+            *deadline += Duration::from_nanos((period.as_nanos() * periods) as u64);
+        }
+
+        Some(true)
+    }
+
+    pub(crate) fn duty_history(&self, mock_index: usize) -> &[u16] {
+        &self.mocks[mock_index].duty_history
+    }
+
+    pub(crate) fn push_duty(&mut self, mock_index: usize, duty: u16) {
+        self.mocks[mock_index].duty_history.push(duty);
+    }
+
+    pub(crate) fn max_duty(&self, mock_index: usize) -> u16 {
+        self.mocks[mock_index].max_duty
+    }
+
+    pub(crate) fn set_max_duty(&mut self, mock_index: usize, max_duty: u16) {
+        self.mocks[mock_index].max_duty = max_duty;
+    }
+
+    pub(crate) fn set_output_state(&mut self, mock_index: usize, output_state: PinState) {
+        self.mocks[mock_index].output_state = output_state;
+    }
+
+    /// Verifies a call, returning `None` if it was recorded as a mismatch
+    pub(crate) fn verify_call(&mut self, mock_index: usize, call: &Call) -> Option<Expectation> {
+        let position = self.call_count;
+        self.call_count += 1;
+
+        match self.find(mock_index, call) {
+            Ok(expectation) => Some(expectation),
+            Err(reason) => {
+                self.report(Mismatch {
+                    position,
+                    mock: self.mock_id(mock_index),
+                    call: call.clone(),
+                    reason,
+                });
+
+                None
+            }
+        }
+    }
+
+    /// Verifies a call only if a matching expectation is pending, used for optional calls
+    pub(crate) fn try_verify_call(
+        &mut self,
+        mock_index: usize,
+        call: &Call,
+    ) -> Option<Expectation> {
+        let expectation = self.find(mock_index, call).ok()?;
+        self.call_count += 1;
+
+        Some(expectation)
+    }
+
+    /// Reports an expected call that left the output pin at the wrong level
+    pub(crate) fn report_state(
+        &mut self,
+        mock_index: usize,
+        call: &Call,
+        expectation: Expectation,
+        output_state: PinState,
+    ) {
+        self.report(Mismatch {
+            position: self.call_count - 1,
+            mock: self.mock_id(mock_index),
+            call: call.clone(),
+            reason: MismatchReason::WrongState(expectation, output_state),
+        });
+    }
+
+    fn report(&mut self, mismatch: Mismatch) {
+        if self.panic_on_mismatch {
+            panic!("{}", mismatch);
+        }
+
+        self.mismatches.push(mismatch);
+    }
+
+    fn find(&mut self, mock_index: usize, call: &Call) -> Result<Expectation, MismatchReason> {
+        match self.order {
+            ExpectationOrder::Strict | ExpectationOrder::PerMock => {
+                self.find_in_order(mock_index, call)
+            }
+            ExpectationOrder::Unordered => self.find_unordered(mock_index, call),
+        }
+    }
+
+    fn find_in_order(
+        &mut self,
+        mock_index: usize,
+        call: &Call,
+    ) -> Result<Expectation, MismatchReason> {
+        let strict = self.order == ExpectationOrder::Strict;
+        let start = if strict { self.cursor } else { 0 };
+
+        for position in start..self.expectations.len() {
+            let entry = &mut self.expectations[position];
+
+            if entry.is_saturated() || (!strict && entry.mock_index != mock_index) {
+                continue;
+            }
+
+            if entry.mock_index == mock_index && call.accepts(&entry.expectation) {
+                entry.calls += 1;
+
+                if strict {
+                    self.cursor = if entry.is_saturated() {
+                        position + 1
+                    } else {
+                        position
+                    };
+                }
+
+                return Ok(entry.expectation.clone());
+            }
+
+            if entry.is_satisfied() {
+                continue;
+            }
+
+            if entry.mock_index != mock_index {
+                let expected_mock_index = entry.mock_index;
+                let expectation = entry.expectation.clone();
+
+                return Err(MismatchReason::WrongMock(
+                    self.mock_id(expected_mock_index),
+                    expectation,
+                ));
+            }
+
+            return Err(MismatchReason::Unexpected(entry.expectation.clone()));
+        }
+
+        Err(MismatchReason::NothingExpected)
+    }
+
+    fn find_unordered(
+        &mut self,
+        mock_index: usize,
+        call: &Call,
+    ) -> Result<Expectation, MismatchReason> {
+        let compatible = |entry: &ExpectationEntry| {
+            !entry.is_saturated()
+                && entry.mock_index == mock_index
+                && call.accepts(&entry.expectation)
+        };
+
+        // Prefer expectations that still need calls over already satisfied ones
+        let position = self
+            .expectations
+            .iter()
+            .position(|entry| compatible(entry) && !entry.is_satisfied())
+            .or_else(|| self.expectations.iter().position(compatible));
+
+        if let Some(position) = position {
+            let entry = &mut self.expectations[position];
+            entry.calls += 1;
+
+            return Ok(entry.expectation.clone());
+        }
+
+        let pending: Vec<Expectation> = self
+            .expectations
+            .iter()
+            .filter(|entry| !entry.is_saturated() && entry.mock_index == mock_index)
+            .map(|entry| entry.expectation.clone())
+            .collect();
+
+        if pending.is_empty() {
+            Err(MismatchReason::NothingExpected)
+        } else {
+            Err(MismatchReason::UnexpectedAnyOf(pending))
+        }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
     use alloc::vec;
 
-    use embedded_hal::digital::v2::{InputPin, OutputPin};
-    use ross_protocol::interface::Interface;
+    use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+    use embedded_hal::can::Can;
+    use embedded_hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
+    use embedded_hal::timer::CountDown;
+    use embedded_hal::PwmPin;
+    use ross_protocol::convert_packet::ConvertPacket;
+    use ross_protocol::event::button::ButtonPressedEvent;
+    use ross_protocol::interface::can::CanError;
+    use ross_protocol::interface::{Interface, InterfaceError};
+    use ross_protocol::packet::PacketBuilderError;
 
     #[test]
     #[should_panic(expected = "Did not expect call to try_get_packet, nothing was expected")]
@@ -462,7 +1064,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Mock with index 1 cannot verify expectation with index 0")]
+    #[should_panic(expected = "Did not expect call to is_high, nothing was expected")]
     fn mismatching_expectation_and_mock_indexes_test() {
         let tracker = ExpectationTracker::new();
         let mock0 = ExpectationTracker::mock(tracker.clone());
@@ -476,4 +1078,1406 @@ mod tests {
 
         mock1.is_high().unwrap();
     }
+
+    #[test]
+    #[should_panic(expected = "Mock with index 1 cannot verify expectation with index 0")]
+    fn strict_mismatching_expectation_and_mock_indexes_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Strict);
+        let mock0 = ExpectationTracker::mock(tracker.clone());
+        let mock1 = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock0,
+            Expectation::InputPin(InputPinExpectation::IsHigh),
+        );
+
+        mock1.is_high().unwrap();
+    }
+
+    #[test]
+    fn unordered_interleaved_mocks_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Unordered);
+        let mut interface_mock = ExpectationTracker::mock(tracker.clone());
+        let input_pin_mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &interface_mock,
+            Expectation::Interface(InterfaceExpectation::ReceivedPacket(Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x11, 0x11, 0x11],
+            })),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &input_pin_mock,
+            Expectation::InputPin(InputPinExpectation::IsHigh),
+        );
+
+        let result = input_pin_mock.is_high().unwrap();
+        assert_eq!(result, true);
+
+        let packet = interface_mock.try_get_packet().unwrap();
+        assert_eq!(packet.device_address, 0x1111);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn unordered_sent_packets_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Unordered);
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::SentPacket(Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x11, 0x11, 0x11],
+            })),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::SentPacket(Packet {
+                is_error: false,
+                device_address: 0x2222,
+                data: vec![0x22, 0x22, 0x22],
+            })),
+        );
+
+        mock.try_send_packet(&Packet {
+            is_error: false,
+            device_address: 0x2222,
+            data: vec![0x22, 0x22, 0x22],
+        })
+        .unwrap();
+        mock.try_send_packet(&Packet {
+            is_error: false,
+            device_address: 0x1111,
+            data: vec![0x11, 0x11, 0x11],
+        })
+        .unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Did not expect call to set_low, expected one of: [OutputPin(SetHigh)]"
+    )]
+    fn unordered_unexpected_call_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Unordered);
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        mock.set_low().unwrap();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "#0 mock with index 0: OutputPin(SetHigh) (expected exactly 1 calls, received 0)"
+    )]
+    fn unordered_unconsumed_expectation_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Unordered);
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetLow),
+        );
+
+        mock.set_low().unwrap();
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn per_mock_independent_queues_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::PerMock);
+        let mut output_pin_mock = ExpectationTracker::mock(tracker.clone());
+        let mut interface_mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &output_pin_mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &output_pin_mock,
+            Expectation::OutputPin(OutputPinExpectation::SetLow),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &interface_mock,
+            Expectation::Interface(InterfaceExpectation::SentPacket(Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x11, 0x11, 0x11],
+            })),
+        );
+
+        interface_mock
+            .try_send_packet(&Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x11, 0x11, 0x11],
+            })
+            .unwrap();
+        output_pin_mock.set_high().unwrap();
+        output_pin_mock.set_low().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(expected = "Did not expect call to set_low, expected: OutputPin(SetHigh)")]
+    fn per_mock_out_of_order_call_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::PerMock);
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetLow),
+        );
+
+        mock.set_low().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Did not expect call to is_high, nothing was expected")]
+    fn per_mock_other_mock_expectation_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::PerMock);
+        let mock0 = ExpectationTracker::mock(tracker.clone());
+        let mock1 = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock0,
+            Expectation::InputPin(InputPinExpectation::IsHigh),
+        );
+
+        mock1.is_high().unwrap();
+    }
+
+    #[test]
+    fn times_exactly_polling_test() {
+        let tracker = ExpectationTracker::new();
+        let input_pin_mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect_times(
+            tracker.clone(),
+            &input_pin_mock,
+            Expectation::InputPin(InputPinExpectation::IsLow),
+            Times::Exactly(500),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &input_pin_mock,
+            Expectation::InputPin(InputPinExpectation::IsHigh),
+        );
+
+        let mut polls = 0;
+
+        while !input_pin_mock.is_high().unwrap() {
+            polls += 1;
+        }
+
+        assert_eq!(polls, 500);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn times_at_least_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect_times(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+            Times::AtLeast(1),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetLow),
+        );
+
+        mock.set_high().unwrap();
+        mock.set_high().unwrap();
+        mock.set_high().unwrap();
+        mock.set_low().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn times_at_most_and_any_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect_times(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+            Times::AtMost(2),
+        );
+        ExpectationTracker::expect_times(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::SentPacket(Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x11, 0x11, 0x11],
+            })),
+            Times::Any,
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetLow),
+        );
+
+        mock.set_low().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(expected = "Did not expect call to set_high, expected: OutputPin(SetLow)")]
+    fn times_at_most_exceeded_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect_times(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+            Times::AtMost(2),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetLow),
+        );
+
+        mock.set_high().unwrap();
+        mock.set_high().unwrap();
+        mock.set_high().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected exactly 2 calls, received 1")]
+    fn times_exactly_not_reached_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect_times(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+            Times::Exactly(2),
+        );
+
+        mock.set_high().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn stateful_output_pin_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetLow),
+        );
+
+        assert_eq!(mock.is_set_high().unwrap(), false);
+        assert_eq!(mock.is_set_low().unwrap(), true);
+
+        mock.set_high().unwrap();
+
+        assert_eq!(mock.is_set_high().unwrap(), true);
+        assert_eq!(mock.is_set_low().unwrap(), false);
+
+        mock.set_low().unwrap();
+
+        assert_eq!(mock.is_set_high().unwrap(), false);
+        assert_eq!(mock.is_set_low().unwrap(), true);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn stateful_output_pin_initial_state_test() {
+        let tracker = ExpectationTracker::new();
+        let mock = ExpectationTracker::mock_with_state(tracker.clone(), PinState::High);
+
+        assert_eq!(mock.is_set_high().unwrap(), true);
+        assert_eq!(mock.is_set_low().unwrap(), false);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn stateful_output_pin_read_back_expectation_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::IsSetHigh),
+        );
+
+        mock.set_high().unwrap();
+
+        assert_eq!(mock.is_set_low().unwrap(), false);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Output pin with index 0 is set low after call to is_set_high, expected: OutputPin(IsSetHigh)"
+    )]
+    fn stateful_output_pin_read_back_mismatch_test() {
+        let tracker = ExpectationTracker::new();
+        let mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::IsSetHigh),
+        );
+
+        mock.is_set_high().unwrap();
+    }
+
+    #[test]
+    fn toggle_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::Toggle),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::ToggleTo(PinState::Low)),
+        );
+
+        mock.toggle().unwrap();
+        assert_eq!(mock.is_set_high().unwrap(), true);
+
+        mock.toggle().unwrap();
+        assert_eq!(mock.is_set_high().unwrap(), false);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Output pin with index 0 is set low after call to toggle, expected: OutputPin(ToggleTo(High))"
+    )]
+    fn toggle_to_mismatch_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock_with_state(tracker.clone(), PinState::High);
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::ToggleTo(PinState::High)),
+        );
+
+        mock.toggle().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Did not expect call to toggle, nothing was expected")]
+    fn unexpected_call_to_toggle_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        mock.toggle().unwrap();
+    }
+
+    #[test]
+    fn verify_without_mismatches_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        mock.set_high().unwrap();
+
+        assert_eq!(tracker.borrow().verify(), Ok(()));
+    }
+
+    #[test]
+    fn mismatching_input_pin_reads_low_test() {
+        let tracker = ExpectationTracker::new();
+        tracker.borrow_mut().set_panic_on_mismatch(false);
+
+        let button = ExpectationTracker::mock(tracker.clone());
+
+        assert_eq!(button.is_high().unwrap(), false);
+        assert_eq!(button.is_low().unwrap(), true);
+        assert_eq!(tracker.borrow().mismatches().len(), 2);
+    }
+
+    #[test]
+    fn verify_recorded_mismatches_test() {
+        let tracker = ExpectationTracker::new();
+        tracker.borrow_mut().set_panic_on_mismatch(false);
+
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::SentPacket(Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x11, 0x11, 0x11],
+            })),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        mock.try_send_packet(&Packet {
+            is_error: false,
+            device_address: 0x2222,
+            data: vec![0x22, 0x22, 0x22],
+        })
+        .unwrap();
+        assert!(mock.try_get_packet().is_err());
+
+        let report = tracker.borrow().verify().unwrap_err();
+
+        assert_eq!(report.mismatches.len(), 2);
+        assert_eq!(report.mismatches[0].position, 0);
+        assert_eq!(report.mismatches[0].mock.index, 0);
+        assert_eq!(
+            report.mismatches[0].call,
+            Call::TrySendPacket(Packet {
+                is_error: false,
+                device_address: 0x2222,
+                data: vec![0x22, 0x22, 0x22],
+            })
+        );
+        assert_eq!(
+            report.mismatches[0].expected(),
+            Some(&Expectation::Interface(InterfaceExpectation::SentPacket(
+                Packet {
+                    is_error: false,
+                    device_address: 0x1111,
+                    data: vec![0x11, 0x11, 0x11],
+                }
+            )))
+        );
+        assert_eq!(report.mismatches[1].position, 1);
+        assert_eq!(report.mismatches[1].call, Call::TryGetPacket);
+        assert_eq!(report.pending.len(), 2);
+        assert_eq!(
+            report.pending[1].expectation,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh)
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "call 0: Did not expect call to set_low, expected: OutputPin(SetHigh)"
+    )]
+    fn done_with_recorded_mismatches_test() {
+        let tracker = ExpectationTracker::new();
+        tracker.borrow_mut().set_panic_on_mismatch(false);
+
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        mock.set_low().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "#1 mock with index 0: SentPacket(is_error: true, device_address: 0x1111, data: [0x11, 0xab]) (expected exactly 1 calls, received 0)"
+    )]
+    fn done_lists_pending_expectations_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::SentPacket(Packet {
+                is_error: true,
+                device_address: 0x1111,
+                data: vec![0x11, 0xab],
+            })),
+        );
+
+        mock.set_high().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "call 1: unexpected extra call to set_low() on mock with index 0, no expectations were left"
+    )]
+    fn done_flags_extra_calls_test() {
+        let tracker = ExpectationTracker::new();
+        tracker.borrow_mut().set_panic_on_mismatch(false);
+
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        mock.set_high().unwrap();
+        mock.set_low().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Mock \"relay_1\" with index 1 cannot verify expectation \"button\" with index 0"
+    )]
+    fn named_mock_mismatching_mock_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Strict);
+        let button = ExpectationTracker::named_mock(tracker.clone(), "button");
+        let relay = ExpectationTracker::named_mock(tracker.clone(), "relay_1");
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &button,
+            Expectation::InputPin(InputPinExpectation::IsHigh),
+        );
+
+        relay.is_high().unwrap();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Did not expect call to set_low on \"relay_1\", nothing was expected"
+    )]
+    fn named_mock_unexpected_call_test() {
+        let tracker = ExpectationTracker::new();
+        let mut relay = ExpectationTracker::named_mock(tracker.clone(), "relay_1");
+
+        relay.set_low().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "#0 mock \"relay_1\" with index 0: OutputPin(SetHigh)")]
+    fn named_mock_done_test() {
+        let tracker = ExpectationTracker::new();
+        let relay = ExpectationTracker::named_mock(tracker.clone(), "relay_1");
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &relay,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn guard_verifies_on_drop_test() {
+        let (tracker, _guard) = ExpectationTracker::new_guarded();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        mock.set_high().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[should_panic(expected = "Verification failed with 1 pending expectations")]
+    fn guard_pending_expectation_test() {
+        let (tracker, _guard) = ExpectationTracker::new_guarded();
+        let mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn guard_dismiss_test() {
+        let (tracker, guard) = ExpectationTracker::new_guarded();
+        let mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        guard.dismiss();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[should_panic(expected = "Did not expect call to set_low, expected: OutputPin(SetHigh)")]
+    fn guard_does_not_double_panic_test() {
+        let (tracker, _guard) = ExpectationTracker::new_guarded();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        mock.set_low().unwrap();
+    }
+
+    #[test]
+    fn receive_error_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect_times(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::ReceiveError(
+                MockInterfaceError::NoPacketReceived,
+            )),
+            Times::Exactly(2),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::ReceiveError(
+                MockInterfaceError::BuilderOutOfOrder,
+            )),
+        );
+
+        assert!(matches!(
+            mock.try_get_packet(),
+            Err(InterfaceError::NoPacketReceived)
+        ));
+        assert!(matches!(
+            mock.try_get_packet(),
+            Err(InterfaceError::NoPacketReceived)
+        ));
+        assert!(matches!(
+            mock.try_get_packet(),
+            Err(InterfaceError::BuilderError(PacketBuilderError::OutOfOrder))
+        ));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn send_error_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::SendError(
+                MockInterfaceError::CanMailboxFull,
+            )),
+        );
+
+        let result = mock.try_send_packet(&Packet {
+            is_error: false,
+            device_address: 0x1111,
+            data: vec![0x11, 0x11, 0x11],
+        });
+
+        assert!(matches!(
+            result,
+            Err(InterfaceError::CanError(CanError::MailboxFull))
+        ));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn sent_packet_matching_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::SentPacketMatching(vec![
+                PacketMatcher::AddressInRange(0x1000..=0x1fff),
+                PacketMatcher::DataPrefix(vec![0x00, 0x05]),
+                PacketMatcher::predicate("counter is odd", |packet| packet.data[2] % 2 == 1),
+            ])),
+        );
+
+        mock.try_send_packet(&Packet {
+            is_error: false,
+            device_address: 0x1111,
+            data: vec![0x00, 0x05, 0x37],
+        })
+        .unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(expected = "failed matchers: data[0] == 0x01, counter is odd")]
+    fn sent_packet_matching_mismatch_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::SentPacketMatching(vec![
+                PacketMatcher::AnyAddress,
+                PacketMatcher::ByteAt(0, 0x01),
+                PacketMatcher::predicate("counter is odd", |packet| packet.data[1] % 2 == 1),
+            ])),
+        );
+
+        mock.try_send_packet(&Packet {
+            is_error: false,
+            device_address: 0x1111,
+            data: vec![0x02, 0x02],
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn event_expectation_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::received_event(&ButtonPressedEvent {
+                receiver_address: 0x1111,
+                button_address: 0x2222,
+                index: 1,
+            })),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::sent_event(&ButtonPressedEvent {
+                receiver_address: 0x3333,
+                button_address: 0x1111,
+                index: 2,
+            })),
+        );
+
+        let packet = mock.try_get_packet().unwrap();
+        let event = ButtonPressedEvent::try_from_packet(&packet).unwrap();
+
+        assert_eq!(event.receiver_address, 0x1111);
+        assert_eq!(event.button_address, 0x2222);
+        assert_eq!(event.index, 1);
+
+        mock.try_send_packet(
+            &ButtonPressedEvent {
+                receiver_address: 0x3333,
+                button_address: 0x1111,
+                index: 2,
+            }
+            .to_packet(),
+        )
+        .unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "sent: ButtonPressedEvent { receiver_address: 13107, button_address: 4369, index: 3 }"
+    )]
+    fn event_expectation_mismatch_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Interface(InterfaceExpectation::sent_event(&ButtonPressedEvent {
+                receiver_address: 0x3333,
+                button_address: 0x1111,
+                index: 2,
+            })),
+        );
+
+        mock.try_send_packet(
+            &ButtonPressedEvent {
+                receiver_address: 0x3333,
+                button_address: 0x1111,
+                index: 3,
+            }
+            .to_packet(),
+        )
+        .unwrap();
+    }
+
+    fn can_frame(id: u32, data: &[u8]) -> CanFrame {
+        CanFrame(bxcan::Frame::new_data(
+            bxcan::ExtendedId::new(id).unwrap(),
+            bxcan::Data::new(data).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn can_transmit_receive_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::Transmitted(can_frame(0x1800_1111, &[0x01]))),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::Received(can_frame(0x1800_2222, &[0x02]))),
+        );
+
+        assert!(matches!(mock.receive(), Err(nb::Error::WouldBlock)));
+        assert_eq!(mock.transmit(&can_frame(0x1800_1111, &[0x01])), Ok(None));
+        assert_eq!(mock.receive(), Ok(can_frame(0x1800_2222, &[0x02])));
+        assert!(matches!(mock.receive(), Err(nb::Error::WouldBlock)));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn can_mailbox_and_error_injection_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::TransmitBlocked),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::MailboxFull(can_frame(0x1800_3333, &[]))),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::TransmitError(ErrorKind::Acknowledge)),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::ReceiveError(ErrorKind::Overrun)),
+        );
+
+        let frame = can_frame(0x1800_1111, &[0x01]);
+
+        assert_eq!(mock.transmit(&frame), Err(nb::Error::WouldBlock));
+        assert_eq!(mock.transmit(&frame), Ok(Some(can_frame(0x1800_3333, &[]))));
+        assert_eq!(
+            mock.transmit(&frame),
+            Err(nb::Error::Other(ErrorKind::Acknowledge))
+        );
+        assert_eq!(mock.receive(), Err(nb::Error::Other(ErrorKind::Overrun)));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Did not expect call to transmit, expected: Can(Transmitted(CanFrame(Frame"
+    )]
+    fn can_unexpected_transmit_test() {
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &mock,
+            Expectation::Can(CanExpectation::Transmitted(can_frame(0x1800_1111, &[0x01]))),
+        );
+
+        mock.transmit(&can_frame(0x1800_1111, &[0x02])).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "sync")]
+    fn sync_mock_in_thread_test() {
+        let tracker = ExpectationTracker::new_sync();
+        let _guard = ExpectationTracker::guard(tracker.clone());
+        let mut isr = ExpectationTracker::named_mock(tracker.clone(), "isr");
+        let mut relay = ExpectationTracker::named_mock(tracker.clone(), "relay");
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &isr,
+            Expectation::Interface(InterfaceExpectation::ReceivedPacket(Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x01],
+            })),
+        );
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &relay,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        let packet = std::thread::spawn(move || isr.try_get_packet().unwrap())
+            .join()
+            .unwrap();
+
+        assert_eq!(packet.data, vec![0x01]);
+        relay.set_high().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "sync")]
+    fn sync_mismatch_in_thread_test() {
+        let tracker = ExpectationTracker::new_sync();
+        let mut relay: SyncMock = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &relay,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh),
+        );
+
+        let result = std::thread::spawn(move || relay.set_low().unwrap()).join();
+
+        assert!(result.is_err());
+        assert_eq!(
+            tracker
+                .with(|tracker| tracker.verify())
+                .unwrap_err()
+                .pending[0]
+                .expectation,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh)
+        );
+    }
+
+    #[test]
+    fn delay_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Strict);
+        let mut relay = ExpectationTracker::mock(tracker.clone());
+        let mut delay = ExpectationTracker::mock(tracker.clone());
+
+        relay.expect().set_high();
+        delay.expect().delay(Duration::from_millis(10));
+        relay.expect().set_low();
+        delay
+            .expect()
+            .delay_between(Duration::from_micros(100), Duration::from_micros(200))
+            .times(2);
+
+        relay.set_high().unwrap();
+        delay.delay_ms(10u8);
+        relay.set_low().unwrap();
+        delay.delay_us(150u32);
+        delay.delay_us(200u16);
+
+        assert_eq!(tracker.borrow().now(), Duration::from_micros(10_350));
+
+        tracker.borrow_mut().advance(Duration::from_secs(1));
+        assert_eq!(tracker.borrow().now(), Duration::from_micros(1_010_350));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(expected = "Mock with index 0 cannot verify expectation with index 1")]
+    fn delay_order_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Strict);
+        let mut relay = ExpectationTracker::mock(tracker.clone());
+        let mut delay = ExpectationTracker::mock(tracker.clone());
+
+        relay.expect().set_high();
+        delay.expect().delay(Duration::from_millis(10));
+        relay.expect().set_low();
+
+        relay.set_high().unwrap();
+        relay.set_low().unwrap();
+        delay.delay_ms(10u8);
+    }
+
+    #[test]
+    fn delay_mismatch_test() {
+        let tracker = ExpectationTracker::new();
+        let mut delay = ExpectationTracker::mock(tracker.clone());
+        tracker.borrow_mut().set_panic_on_mismatch(false);
+
+        delay.expect().delay_at_least(Duration::from_millis(5));
+
+        delay.delay_ms(1u32);
+
+        let report = tracker.borrow().verify().unwrap_err();
+
+        assert_eq!(
+            report.mismatches[0].call,
+            Call::Delay(Duration::from_millis(1))
+        );
+        assert_eq!(tracker.borrow().now(), Duration::from_millis(1));
+        assert!(report
+            .to_string()
+            .contains("Did not expect call to delay, expected: Delay(AtLeast(5ms))"));
+    }
+
+    #[test]
+    fn waveform_test() {
+        let tracker = ExpectationTracker::new();
+        let button = ExpectationTracker::mock(tracker.clone());
+        let mut relay = ExpectationTracker::mock(tracker.clone());
+
+        relay.expect().set_high();
+
+        tracker.borrow_mut().advance(Duration::from_millis(50));
+        button.drive(
+            Waveform::new(PinState::Low)
+                .low(Duration::from_millis(20))
+                .high(Duration::from_millis(300)),
+        );
+
+        let mut high_reads = 0;
+
+        for _ in 0..100 {
+            if button.is_high().unwrap() {
+                high_reads += 1;
+            }
+
+            assert_eq!(button.is_low().unwrap(), !button.is_high().unwrap());
+            tracker.borrow_mut().advance(Duration::from_millis(7));
+        }
+
+        // Reads at 21, 28, ... 693 ms after the start, the level is held after the waveform ends
+        assert_eq!(high_reads, 97);
+
+        relay.set_high().unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn bounced_button_test() {
+        let tracker = ExpectationTracker::new();
+        let button = ExpectationTracker::mock(tracker.clone());
+
+        button.drive(
+            ButtonStimulus::new(PinState::Low)
+                .with_bounce(
+                    Bounce {
+                        count: 3..=6,
+                        duration: Duration::from_micros(100)..=Duration::from_micros(900),
+                    },
+                    1234,
+                )
+                .idle(Duration::from_millis(20))
+                .double_click()
+                .idle(Duration::from_millis(20))
+                .long_press()
+                .build(),
+        );
+
+        let mut raw_edges = 0;
+        let mut raw_pressed = false;
+        let mut pressed = false;
+        let mut stable_reads = 0;
+        let mut presses = 0;
+
+        // Samples every 250 us and accepts a level after 5 ms without changes
+        for _ in 0..10000 {
+            let sample = button.is_low().unwrap();
+
+            if sample != raw_pressed {
+                raw_pressed = sample;
+                raw_edges += 1;
+                stable_reads = 0;
+            } else if stable_reads < 20 {
+                stable_reads += 1;
+            } else if raw_pressed != pressed {
+                pressed = raw_pressed;
+
+                if pressed {
+                    presses += 1;
+                }
+            }
+
+            tracker.borrow_mut().advance(Duration::from_micros(250));
+        }
+
+        assert!(raw_edges > 6);
+        assert_eq!(presses, 3);
+        assert_eq!(pressed, false);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(expected = "Did not expect call to is_high, nothing was expected")]
+    fn waveform_release_test() {
+        let tracker = ExpectationTracker::new();
+        let button = ExpectationTracker::mock(tracker.clone());
+
+        button.drive(Waveform::new(PinState::High));
+        assert_eq!(button.is_high().unwrap(), true);
+
+        button.release();
+        button.is_high().unwrap();
+    }
+
+    #[test]
+    fn timer_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Strict);
+        let mut timer = ExpectationTracker::mock(tracker.clone());
+        let mut delay = ExpectationTracker::mock(tracker.clone());
+
+        timer.expect().start(Duration::from_millis(10));
+        delay.expect().delay(Duration::from_millis(4)).times(5);
+
+        timer.start(Duration::from_millis(10));
+
+        let mut expiries = vec![];
+
+        for _ in 0..5 {
+            delay.delay_ms(4u8);
+
+            if timer.wait().is_ok() {
+                expiries.push(tracker.borrow().now());
+            }
+        }
+
+        // Periodic deadlines do not drift with the polling interval
+        assert_eq!(
+            expiries,
+            vec![Duration::from_millis(12), Duration::from_millis(20)]
+        );
+        assert_eq!(timer.wait(), Err(nb::Error::WouldBlock));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(expected = "Mock with index 1 cannot verify expectation with index 0")]
+    fn timer_order_test() {
+        let tracker = ExpectationTracker::with_order(ExpectationOrder::Strict);
+        let mut timer = ExpectationTracker::mock(tracker.clone());
+        let mut delay = ExpectationTracker::mock(tracker.clone());
+
+        timer.expect().start(Duration::from_millis(10));
+        delay.expect().delay(Duration::from_millis(4));
+
+        delay.delay_ms(4u8);
+        timer.start(Duration::from_millis(10));
+    }
+
+    #[test]
+    fn timer_missed_periods_test() {
+        let tracker = ExpectationTracker::new();
+        let mut timer = ExpectationTracker::mock(tracker.clone());
+
+        timer.expect().start(Duration::from_millis(10));
+
+        timer.start(Duration::from_millis(10));
+        tracker.borrow_mut().advance(Duration::from_millis(35));
+
+        // Missed periods are reported as a single expiry
+        assert_eq!(timer.wait(), Ok(()));
+        assert_eq!(timer.wait(), Err(nb::Error::WouldBlock));
+
+        tracker.borrow_mut().advance(Duration::from_millis(4));
+        assert_eq!(timer.wait(), Err(nb::Error::WouldBlock));
+        tracker.borrow_mut().advance(Duration::from_millis(1));
+        assert_eq!(timer.wait(), Ok(()));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(expected = "Did not expect call to wait, nothing was expected")]
+    fn timer_wait_without_start_test() {
+        let tracker = ExpectationTracker::new();
+        let mut timer = ExpectationTracker::mock(tracker.clone());
+
+        let _ = timer.wait();
+    }
+
+    #[test]
+    fn timer_scripted_expiry_test() {
+        let tracker = ExpectationTracker::new();
+        let mut timer = ExpectationTracker::mock(tracker.clone());
+
+        timer.expect().start(Duration::from_secs(1));
+        timer.expect().expire().times(2);
+
+        timer.start(Duration::from_secs(1));
+        tracker.borrow_mut().advance(Duration::from_millis(300));
+
+        assert_eq!(timer.wait(), Ok(()));
+        assert_eq!(timer.wait(), Ok(()));
+        assert_eq!(timer.wait(), Err(nb::Error::WouldBlock));
+
+        // The scripted expiry restarted the period
+        tracker.borrow_mut().advance(Duration::from_millis(999));
+        assert_eq!(timer.wait(), Err(nb::Error::WouldBlock));
+        tracker.borrow_mut().advance(Duration::from_millis(1));
+        assert_eq!(timer.wait(), Ok(()));
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    #[should_panic(expected = "Did not expect call to start, expected: Timer(Started(10ms))")]
+    fn timer_period_mismatch_test() {
+        let tracker = ExpectationTracker::new();
+        let mut timer = ExpectationTracker::mock(tracker.clone());
+
+        timer.expect().start(Duration::from_millis(10));
+
+        timer.start(Duration::from_millis(20));
+    }
+
+    #[test]
+    fn pwm_fade_test() {
+        let tracker = ExpectationTracker::new();
+        let mut dimmer = ExpectationTracker::mock(tracker.clone());
+
+        dimmer.expect().get_max_duty(1000);
+        dimmer.expect().enable();
+
+        for step in 1..=4 {
+            dimmer.expect().set_duty_within(step * 250, 5);
+        }
+
+        dimmer.expect().disable();
+
+        let max_duty = dimmer.get_max_duty();
+        dimmer.enable();
+
+        for step in 1..=4u32 {
+            dimmer.set_duty((max_duty as u32 * step / 4) as u16 - 1);
+        }
+
+        dimmer.disable();
+
+        assert_eq!(dimmer.duty_history(), vec![249, 499, 749, 999]);
+        assert_eq!(dimmer.get_duty(), 999);
+        assert_eq!(dimmer.get_max_duty(), 1000);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn pwm_get_duty_test() {
+        let tracker = ExpectationTracker::new();
+        let mut dimmer = ExpectationTracker::mock(tracker.clone());
+
+        dimmer.expect().set_duty(300);
+        dimmer.expect().get_duty(200);
+
+        assert_eq!(dimmer.get_duty(), 0);
+        assert_eq!(dimmer.get_max_duty(), u16::MAX);
+
+        dimmer.set_duty(300);
+
+        assert_eq!(dimmer.get_duty(), 200);
+        assert_eq!(dimmer.get_duty(), 300);
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn pwm_duty_mismatch_test() {
+        let tracker = ExpectationTracker::new();
+        let mut dimmer = ExpectationTracker::mock(tracker.clone());
+
+        tracker.borrow_mut().set_panic_on_mismatch(false);
+        dimmer.expect().set_duty_between(100, 200);
+
+        dimmer.set_duty(201);
+
+        let report = tracker.borrow().verify().unwrap_err();
+
+        assert_eq!(report.mismatches[0].call, Call::SetDuty(201));
+        assert!(report
+            .to_string()
+            .contains("Did not expect call to set_duty, expected: Pwm(SetDutyBetween(100, 200))"));
+        assert_eq!(dimmer.duty_history(), vec![201]);
+    }
 }
@@ -0,0 +1,275 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use core::ops::RangeInclusive;
+
+use ross_protocol::packet::Packet;
+
+use crate::report::{fmt_bytes, fmt_matchers};
+
+/// Arbitrary packet check with a description used in failure messages
+#[derive(Clone)]
+pub struct PacketPredicate {
+    description: String,
+    predicate: Arc<dyn Fn(&Packet) -> bool + Send + Sync>,
+}
+
+impl PacketPredicate {
+    pub fn new<F>(description: &str, predicate: F) -> Self
+    where
+        F: Fn(&Packet) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            description: description.to_string(),
+            predicate: Arc::new(predicate),
+        }
+    }
+}
+
+impl Debug for PacketPredicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:?}", self.description)
+    }
+}
+
+impl PartialEq for PacketPredicate {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.predicate, &other.predicate)
+    }
+}
+
+/// Partial check of a sent packet, used by `InterfaceExpectation::SentPacketMatching`
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketMatcher {
+    AnyAddress,
+    Address(u16),
+    AddressInRange(RangeInclusive<u16>),
+    IsError(bool),
+    Data(Vec<u8>),
+    DataLength(usize),
+    DataPrefix(Vec<u8>),
+    DataSuffix(Vec<u8>),
+    /// Byte at the given offset has the given value
+    ByteAt(usize, u8),
+    /// Bytes starting at the given offset are equal to the value after applying the mask
+    ///
+    /// An empty mask or a mask of another length than the value never matches.
+    Masked {
+        offset: usize,
+        mask: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Not(Box<PacketMatcher>),
+    AnyOf(Vec<PacketMatcher>),
+    Predicate(PacketPredicate),
+}
+
+impl PacketMatcher {
+    pub fn predicate<F>(description: &str, predicate: F) -> Self
+    where
+        F: Fn(&Packet) -> bool + Send + Sync + 'static,
+    {
+        PacketMatcher::Predicate(PacketPredicate::new(description, predicate))
+    }
+
+    pub fn matches(&self, packet: &Packet) -> bool {
+        match self {
+            PacketMatcher::AnyAddress => true,
+            PacketMatcher::Address(address) => packet.device_address == *address,
+            PacketMatcher::AddressInRange(range) => range.contains(&packet.device_address),
+            PacketMatcher::IsError(is_error) => packet.is_error == *is_error,
+            PacketMatcher::Data(data) => packet.data == *data,
+            PacketMatcher::DataLength(len) => packet.data.len() == *len,
+            PacketMatcher::DataPrefix(prefix) => packet.data.starts_with(prefix),
+            PacketMatcher::DataSuffix(suffix) => packet.data.ends_with(suffix),
+            PacketMatcher::ByteAt(offset, value) => packet.data.get(*offset) == Some(value),
+            PacketMatcher::Masked {
+                offset,
+                mask,
+                value,
+            } => {
+                !mask.is_empty()
+                    && mask.len() == value.len()
+                    && mask
+                        .iter()
+                        .zip(value.iter())
+                        .enumerate()
+                        .all(|(i, (mask, value))| match packet.data.get(offset + i) {
+                            Some(byte) => byte & mask == value & mask,
+                            None => false,
+                        })
+            }
+            PacketMatcher::Not(matcher) => !matcher.matches(packet),
+            PacketMatcher::AnyOf(matchers) => {
+                matchers.iter().any(|matcher| matcher.matches(packet))
+            }
+            PacketMatcher::Predicate(predicate) => (predicate.predicate)(packet),
+        }
+    }
+}
+
+impl Display for PacketMatcher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            PacketMatcher::AnyAddress => write!(f, "any device_address"),
+            PacketMatcher::Address(address) => write!(f, "device_address == 0x{:04x}", address),
+            PacketMatcher::AddressInRange(range) => write!(
+                f,
+                "device_address in 0x{:04x}..=0x{:04x}",
+                range.start(),
+                range.end()
+            ),
+            PacketMatcher::IsError(is_error) => write!(f, "is_error == {}", is_error),
+            PacketMatcher::Data(data) => {
+                write!(f, "data == ")?;
+                fmt_bytes(f, data)
+            }
+            PacketMatcher::DataLength(len) => write!(f, "data.len() == {}", len),
+            PacketMatcher::DataPrefix(prefix) => {
+                write!(f, "data starts with ")?;
+                fmt_bytes(f, prefix)
+            }
+            PacketMatcher::DataSuffix(suffix) => {
+                write!(f, "data ends with ")?;
+                fmt_bytes(f, suffix)
+            }
+            PacketMatcher::ByteAt(offset, value) => {
+                write!(f, "data[{}] == 0x{:02x}", offset, value)
+            }
+            PacketMatcher::Masked {
+                offset,
+                mask,
+                value,
+            } => {
+                write!(f, "data[{}..] & ", offset)?;
+                fmt_bytes(f, mask)?;
+                write!(f, " == ")?;
+                fmt_bytes(f, value)
+            }
+            PacketMatcher::Not(matcher) => write!(f, "not ({})", matcher),
+            PacketMatcher::AnyOf(matchers) => {
+                write!(f, "any of (")?;
+                fmt_matchers(f, matchers)?;
+                write!(f, ")")
+            }
+            PacketMatcher::Predicate(predicate) => write!(f, "{}", predicate.description),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    const PACKET: Packet = Packet {
+        is_error: false,
+        device_address: 0x1234,
+        data: Vec::new(),
+    };
+
+    fn packet(data: Vec<u8>) -> Packet {
+        Packet { data, ..PACKET }
+    }
+
+    #[test]
+    fn address_matchers_test() {
+        let packet = packet(vec![]);
+
+        assert!(PacketMatcher::AnyAddress.matches(&packet));
+        assert!(PacketMatcher::Address(0x1234).matches(&packet));
+        assert!(!PacketMatcher::Address(0x4321).matches(&packet));
+        assert!(PacketMatcher::AddressInRange(0x1000..=0x1fff).matches(&packet));
+        assert!(!PacketMatcher::AddressInRange(0x2000..=0x2fff).matches(&packet));
+        assert!(PacketMatcher::IsError(false).matches(&packet));
+    }
+
+    #[test]
+    fn data_matchers_test() {
+        let packet = packet(vec![0x01, 0x02, 0x33, 0x44]);
+
+        assert!(PacketMatcher::DataPrefix(vec![0x01, 0x02]).matches(&packet));
+        assert!(!PacketMatcher::DataPrefix(vec![0x02]).matches(&packet));
+        assert!(PacketMatcher::DataSuffix(vec![0x44]).matches(&packet));
+        assert!(PacketMatcher::ByteAt(2, 0x33).matches(&packet));
+        assert!(!PacketMatcher::ByteAt(4, 0x00).matches(&packet));
+        assert!(PacketMatcher::DataLength(4).matches(&packet));
+        assert!(PacketMatcher::Masked {
+            offset: 2,
+            mask: vec![0xf0, 0x0f],
+            value: vec![0x30, 0x04],
+        }
+        .matches(&packet));
+        assert!(!PacketMatcher::Masked {
+            offset: 3,
+            mask: vec![0xff, 0xff],
+            value: vec![0x44, 0x00],
+        }
+        .matches(&packet));
+    }
+
+    #[test]
+    fn masked_length_mismatch_test() {
+        let packet = packet(vec![0x01]);
+
+        assert!(!PacketMatcher::Masked {
+            offset: 5,
+            mask: vec![],
+            value: vec![0x01, 0x02, 0x03],
+        }
+        .matches(&packet));
+        assert!(!PacketMatcher::Masked {
+            offset: 0,
+            mask: vec![],
+            value: vec![],
+        }
+        .matches(&packet));
+        assert!(!PacketMatcher::Masked {
+            offset: 0,
+            mask: vec![0xff, 0xff],
+            value: vec![0x01],
+        }
+        .matches(&packet));
+    }
+
+    #[test]
+    fn composed_matchers_test() {
+        let packet = packet(vec![0x01]);
+
+        assert!(PacketMatcher::Not(Box::new(PacketMatcher::Address(0x0000))).matches(&packet));
+        assert!(PacketMatcher::AnyOf(vec![
+            PacketMatcher::Address(0x0000),
+            PacketMatcher::ByteAt(0, 0x01),
+        ])
+        .matches(&packet));
+        assert!(
+            PacketMatcher::predicate("odd data length", |packet| packet.data.len() % 2 == 1)
+                .matches(&packet)
+        );
+    }
+
+    #[test]
+    fn matcher_description_test() {
+        assert_eq!(
+            PacketMatcher::AddressInRange(0x1000..=0x1fff).to_string(),
+            "device_address in 0x1000..=0x1fff"
+        );
+        assert_eq!(
+            PacketMatcher::Masked {
+                offset: 1,
+                mask: vec![0xff],
+                value: vec![0x0a],
+            }
+            .to_string(),
+            "data[1..] & [0xff] == [0x0a]"
+        );
+        assert_eq!(
+            PacketMatcher::Not(Box::new(PacketMatcher::predicate("fresh nonce", |_| true)))
+                .to_string(),
+            "not (fresh nonce)"
+        );
+    }
+}
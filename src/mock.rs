@@ -1,205 +1,488 @@
 use alloc::rc::Rc;
+use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::convert::Infallible;
-use embedded_hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin};
+use core::time::Duration;
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::can::{Can, ErrorKind};
+use embedded_hal::digital::v2::{
+    InputPin, OutputPin, PinState, StatefulOutputPin, ToggleableOutputPin,
+};
+use embedded_hal::timer::{CountDown, Periodic};
+use embedded_hal::PwmPin;
+use void::Void;
 
 use ross_protocol::interface::{Interface, InterfaceError};
 use ross_protocol::packet::Packet;
 
 use crate::{
-    Expectation, ExpectationTracker, InputPinExpectation, InterfaceExpectation,
-    OutputPinExpectation,
+    CanExpectation, CanFrame, Expectation, ExpectationBuilder, ExpectationTracker,
+    InputPinExpectation, InterfaceExpectation, OutputPinExpectation, PwmExpectation,
+    TimerExpectation, TrackerHandle, Waveform,
 };
 
+/// A call made to a mock, verified by the expectation tracker
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    TryGetPacket,
+    TrySendPacket(Packet),
+    IsHigh,
+    IsLow,
+    SetHigh,
+    SetLow,
+    IsSetHigh,
+    IsSetLow,
+    Toggle,
+    Transmit(CanFrame),
+    Receive,
+    Delay(Duration),
+    StartTimer(Duration),
+    Wait,
+    Enable,
+    Disable,
+    SetDuty(u16),
+    GetDuty,
+    GetMaxDuty,
+}
+
+impl Call {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Call::TryGetPacket => "try_get_packet",
+            Call::TrySendPacket(_) => "try_send_packet",
+            Call::IsHigh => "is_high",
+            Call::IsLow => "is_low",
+            Call::SetHigh => "set_high",
+            Call::SetLow => "set_low",
+            Call::IsSetHigh => "is_set_high",
+            Call::IsSetLow => "is_set_low",
+            Call::Toggle => "toggle",
+            Call::Transmit(_) => "transmit",
+            Call::Receive => "receive",
+            Call::Delay(_) => "delay",
+            Call::StartTimer(_) => "start",
+            Call::Wait => "wait",
+            Call::Enable => "enable",
+            Call::Disable => "disable",
+            Call::SetDuty(_) => "set_duty",
+            Call::GetDuty => "get_duty",
+            Call::GetMaxDuty => "get_max_duty",
+        }
+    }
+
+    pub(crate) fn accepts(&self, expectation: &Expectation) -> bool {
+        match (self, expectation) {
+            (
+                Call::TryGetPacket,
+                Expectation::Interface(InterfaceExpectation::ReceivedPacket(_)),
+            ) => true,
+            (
+                Call::TrySendPacket(packet),
+                Expectation::Interface(InterfaceExpectation::SentPacket(expected_packet)),
+            ) => expected_packet == packet,
+            (
+                Call::TrySendPacket(packet),
+                Expectation::Interface(InterfaceExpectation::SentPacketMatching(matchers)),
+            ) => matchers.iter().all(|matcher| matcher.matches(packet)),
+            (
+                Call::TrySendPacket(packet),
+                Expectation::Interface(InterfaceExpectation::SentEvent(event)),
+            ) => event.packet() == packet,
+            (
+                Call::TryGetPacket,
+                Expectation::Interface(InterfaceExpectation::ReceivedEvent(_)),
+            ) => true,
+            (Call::TryGetPacket, Expectation::Interface(InterfaceExpectation::ReceiveError(_))) => {
+                true
+            }
+            (
+                Call::TrySendPacket(_),
+                Expectation::Interface(InterfaceExpectation::SendError(_)),
+            ) => true,
+            (Call::IsHigh, Expectation::InputPin(_)) => true,
+            (Call::IsLow, Expectation::InputPin(_)) => true,
+            (Call::SetHigh, Expectation::OutputPin(OutputPinExpectation::SetHigh)) => true,
+            (Call::SetLow, Expectation::OutputPin(OutputPinExpectation::SetLow)) => true,
+            (
+                Call::IsSetHigh | Call::IsSetLow,
+                Expectation::OutputPin(OutputPinExpectation::IsSetHigh),
+            ) => true,
+            (
+                Call::IsSetHigh | Call::IsSetLow,
+                Expectation::OutputPin(OutputPinExpectation::IsSetLow),
+            ) => true,
+            (Call::Toggle, Expectation::OutputPin(OutputPinExpectation::Toggle)) => true,
+            (Call::Toggle, Expectation::OutputPin(OutputPinExpectation::ToggleTo(_))) => true,
+            (
+                Call::Transmit(frame),
+                Expectation::Can(CanExpectation::Transmitted(expected_frame)),
+            ) => expected_frame == frame,
+            (Call::Transmit(_), Expectation::Can(CanExpectation::MailboxFull(_))) => true,
+            (Call::Transmit(_), Expectation::Can(CanExpectation::TransmitBlocked)) => true,
+            (Call::Transmit(_), Expectation::Can(CanExpectation::TransmitError(_))) => true,
+            (Call::Receive, Expectation::Can(CanExpectation::Received(_))) => true,
+            (Call::Receive, Expectation::Can(CanExpectation::ReceiveError(_))) => true,
+            (Call::Delay(duration), Expectation::Delay(expectation)) => {
+                expectation.contains(*duration)
+            }
+            (Call::StartTimer(period), Expectation::Timer(TimerExpectation::Started(expected))) => {
+                expected == period
+            }
+            (Call::Wait, Expectation::Timer(TimerExpectation::Expired)) => true,
+            (Call::Enable, Expectation::Pwm(PwmExpectation::Enable)) => true,
+            (Call::Disable, Expectation::Pwm(PwmExpectation::Disable)) => true,
+            (Call::SetDuty(duty), Expectation::Pwm(PwmExpectation::SetDuty(expected))) => {
+                expected == duty
+            }
+            (Call::SetDuty(duty), Expectation::Pwm(PwmExpectation::SetDutyBetween(min, max))) => {
+                duty >= min && duty <= max
+            }
+            (Call::GetDuty, Expectation::Pwm(PwmExpectation::GetDuty(_))) => true,
+            (Call::GetMaxDuty, Expectation::Pwm(PwmExpectation::GetMaxDuty(_))) => true,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct Mock {
-    expectation_tracker: Rc<RefCell<ExpectationTracker>>,
+pub struct Mock<H: TrackerHandle = Rc<RefCell<ExpectationTracker>>> {
+    expectation_tracker: H,
     index: usize,
 }
 
-impl Mock {
-    pub(crate) fn new(expectation_tracker: Rc<RefCell<ExpectationTracker>>, index: usize) -> Self {
+impl<H: TrackerHandle> Mock<H> {
+    pub(crate) fn new(expectation_tracker: H, index: usize) -> Self {
         Self {
             expectation_tracker,
             index,
         }
     }
 
+    /// Starts adding an expectation for this mock
+    pub fn expect(&self) -> ExpectationBuilder<'_, H> {
+        ExpectationBuilder::new(self)
+    }
+
+    /// Drives the input pin with a waveform starting at the current virtual time
+    ///
+    /// Reads of a driven pin return the waveform level and are not verified against expectations.
+    pub fn drive(&self, waveform: Waveform) {
+        self.expectation_tracker
+            .with(|tracker| tracker.set_waveform(self.index, Some(waveform)));
+    }
+
+    /// Stops driving the input pin, reads are verified against expectations again
+    pub fn release(&self) {
+        self.expectation_tracker
+            .with(|tracker| tracker.set_waveform(self.index, None));
+    }
+
+    /// Duty cycles set on the PWM pin so far, in order
+    pub fn duty_history(&self) -> Vec<u16> {
+        self.expectation_tracker
+            .with(|tracker| tracker.duty_history(self.index).to_vec())
+    }
+
     pub(crate) fn get_index(&self) -> usize {
         self.index
     }
-}
 
-impl Interface for Mock {
-    fn try_get_packet(&mut self) -> Result<Packet, InterfaceError> {
-        let expectation_option = self.expectation_tracker.borrow_mut().next();
-
-        if let Some((index, expectation)) = expectation_option {
-            if self.index == index {
-                if let Expectation::Interface(InterfaceExpectation::ReceivedPacket(packet)) =
-                    expectation
-                {
-                    Ok(packet)
-                } else {
-                    panic!(
-                        "Did not expect call to try_get_packet, expected: {:?}",
-                        expectation
-                    );
+    pub(crate) fn expectation_tracker(&self) -> &H {
+        &self.expectation_tracker
+    }
+
+    fn verify(&self, call: Call) -> Option<Expectation> {
+        self.expectation_tracker
+            .with(|tracker| tracker.verify_call(self.index, &call))
+    }
+
+    fn input_state(&self) -> Option<PinState> {
+        self.expectation_tracker
+            .with(|tracker| tracker.input_state(self.index))
+    }
+
+    /// Level read by `is_high` or `is_low`, a mismatching read returns low
+    fn input_level(&self, call: Call) -> PinState {
+        if let Some(input_state) = self.input_state() {
+            return input_state;
+        }
+
+        match self.verify(call) {
+            Some(Expectation::InputPin(InputPinExpectation::IsHigh)) => PinState::High,
+            Some(Expectation::InputPin(InputPinExpectation::IsLow)) => PinState::Low,
+            Some(_) => unreachable!(),
+            None => PinState::Low,
+        }
+    }
+
+    fn output_state(&self, call: Call) -> PinState {
+        self.expectation_tracker.with(|tracker| {
+            let output_state = tracker.output_state(self.index);
+
+            let expected_state = match tracker.try_verify_call(self.index, &call) {
+                Some(Expectation::OutputPin(OutputPinExpectation::IsSetHigh)) => {
+                    (OutputPinExpectation::IsSetHigh, PinState::High)
+                }
+                Some(Expectation::OutputPin(OutputPinExpectation::IsSetLow)) => {
+                    (OutputPinExpectation::IsSetLow, PinState::Low)
                 }
-            } else {
-                panic!(
-                    "Mock with index {} cannot verify expectation with index {}",
-                    self.index, index
+                _ => return output_state,
+            };
+
+            if output_state != expected_state.1 {
+                tracker.report_state(
+                    self.index,
+                    &call,
+                    Expectation::OutputPin(expected_state.0),
+                    output_state,
                 );
             }
-        } else {
-            panic!("Did not expect call to try_get_packet, nothing was expected")
+
+            output_state
+        })
+    }
+
+    fn delay(&mut self, duration: Duration) {
+        self.verify(Call::Delay(duration));
+        self.expectation_tracker
+            .with(|tracker| tracker.advance(duration));
+    }
+
+    fn set_output_state(&mut self, output_state: PinState) {
+        self.expectation_tracker
+            .with(|tracker| tracker.set_output_state(self.index, output_state));
+    }
+}
+
+impl<H: TrackerHandle> Interface for Mock<H> {
+    fn try_get_packet(&mut self) -> Result<Packet, InterfaceError> {
+        match self.verify(Call::TryGetPacket) {
+            Some(Expectation::Interface(InterfaceExpectation::ReceivedPacket(packet))) => {
+                Ok(packet)
+            }
+            Some(Expectation::Interface(InterfaceExpectation::ReceivedEvent(event))) => {
+                Ok(event.packet().clone())
+            }
+            Some(Expectation::Interface(InterfaceExpectation::ReceiveError(error))) => {
+                Err(error.into())
+            }
+            Some(_) => unreachable!(),
+            None => Err(InterfaceError::NoPacketReceived),
         }
     }
 
     fn try_send_packet(&mut self, packet: &Packet) -> Result<(), InterfaceError> {
-        let expectation_option = self.expectation_tracker.borrow_mut().next();
-
-        if let Some((index, expectation)) = expectation_option {
-            if self.index == index {
-                if let Expectation::Interface(InterfaceExpectation::SentPacket(expected_packet)) =
-                    expectation
-                {
-                    assert_eq!(expected_packet.is_error, packet.is_error);
-                    assert_eq!(expected_packet.device_address, packet.device_address);
-                    assert_eq!(expected_packet.data, packet.data);
-                    Ok(())
-                } else {
-                    panic!(
-                        "Did not expect call to try_send_packet, expected: {:?}",
-                        expectation
-                    );
-                }
-            } else {
-                panic!(
-                    "Mock with index {} cannot verify expectation with index {}",
-                    self.index, index
-                );
+        match self.verify(Call::TrySendPacket(packet.clone())) {
+            Some(Expectation::Interface(InterfaceExpectation::SendError(error))) => {
+                Err(error.into())
             }
-        } else {
-            panic!("Did not expect call to try_send_packet, nothing was expected")
+            _ => Ok(()),
         }
     }
 }
 
-impl InputPin for Mock {
+impl<H: TrackerHandle> InputPin for Mock<H> {
     type Error = Infallible;
 
     fn is_high(&self) -> Result<bool, Self::Error> {
-        let expectation_option = self.expectation_tracker.borrow_mut().next();
-
-        if let Some((index, expectation)) = expectation_option {
-            if self.index == index {
-                if let Expectation::InputPin(input_pin_expectation) = expectation {
-                    match input_pin_expectation {
-                        InputPinExpectation::IsHigh => Ok(true),
-                        InputPinExpectation::IsLow => Ok(false),
-                    }
-                } else {
-                    panic!(
-                        "Did not expect call to is_high, expected: {:?}",
-                        expectation
-                    );
-                }
-            } else {
-                panic!(
-                    "Mock with index {} cannot verify expectation with index {}",
-                    self.index, index
-                );
-            }
-        } else {
-            panic!("Did not expect call to is_high, nothing was expected")
-        }
+        Ok(self.input_level(Call::IsHigh) == PinState::High)
     }
 
     fn is_low(&self) -> Result<bool, Self::Error> {
-        let expectation_option = self.expectation_tracker.borrow_mut().next();
-
-        if let Some((index, expectation)) = expectation_option {
-            if self.index == index {
-                if let Expectation::InputPin(input_pin_expectation) = expectation {
-                    match input_pin_expectation {
-                        InputPinExpectation::IsHigh => Ok(false),
-                        InputPinExpectation::IsLow => Ok(true),
-                    }
-                } else {
-                    panic!("Did not expect call to is_low, expected: {:?}", expectation);
-                }
-            } else {
-                panic!(
-                    "Mock with index {} cannot verify expectation with index {}",
-                    self.index, index
-                );
-            }
-        } else {
-            panic!("Did not expect call to is_low, nothing was expected")
-        }
+        Ok(self.input_level(Call::IsLow) == PinState::Low)
     }
 }
 
-impl OutputPin for Mock {
+impl<H: TrackerHandle> OutputPin for Mock<H> {
     type Error = Infallible;
 
     fn set_high(&mut self) -> Result<(), Self::Error> {
-        let expectation_option = self.expectation_tracker.borrow_mut().next();
-
-        if let Some((index, expectation)) = expectation_option {
-            if self.index == index {
-                if let Expectation::OutputPin(OutputPinExpectation::SetHigh) = expectation {
-                    Ok(())
-                } else {
-                    panic!(
-                        "Did not expect call to set_high, expected: {:?}",
-                        expectation
+        self.verify(Call::SetHigh);
+        self.set_output_state(PinState::High);
+
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.verify(Call::SetLow);
+        self.set_output_state(PinState::Low);
+
+        Ok(())
+    }
+}
+
+impl<H: TrackerHandle> StatefulOutputPin for Mock<H> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.output_state(Call::IsSetHigh) == PinState::High)
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(self.output_state(Call::IsSetLow) == PinState::Low)
+    }
+}
+
+impl<H: TrackerHandle> ToggleableOutputPin for Mock<H> {
+    type Error = Infallible;
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        let expectation = self.verify(Call::Toggle);
+
+        self.expectation_tracker.with(|tracker| {
+            let output_state = !tracker.output_state(self.index);
+
+            if let Some(Expectation::OutputPin(OutputPinExpectation::ToggleTo(expected_state))) =
+                expectation
+            {
+                if output_state != expected_state {
+                    tracker.report_state(
+                        self.index,
+                        &Call::Toggle,
+                        Expectation::OutputPin(OutputPinExpectation::ToggleTo(expected_state)),
+                        output_state,
                     );
                 }
-            } else {
-                panic!(
-                    "Mock with index {} cannot verify expectation with index {}",
-                    self.index, index
-                );
             }
-        } else {
-            panic!("Did not expect call to set_high, nothing was expected")
+
+            tracker.set_output_state(self.index, output_state);
+        });
+
+        Ok(())
+    }
+}
+
+impl<H: TrackerHandle> Can for Mock<H> {
+    type Frame = CanFrame;
+    type Error = ErrorKind;
+
+    fn transmit(&mut self, frame: &Self::Frame) -> nb::Result<Option<Self::Frame>, Self::Error> {
+        match self.verify(Call::Transmit(frame.clone())) {
+            Some(Expectation::Can(CanExpectation::MailboxFull(dequeued_frame))) => {
+                Ok(Some(dequeued_frame))
+            }
+            Some(Expectation::Can(CanExpectation::TransmitBlocked)) => Err(nb::Error::WouldBlock),
+            Some(Expectation::Can(CanExpectation::TransmitError(error))) => {
+                Err(nb::Error::Other(error))
+            }
+            _ => Ok(None),
         }
     }
 
-    fn set_low(&mut self) -> Result<(), Self::Error> {
-        let expectation_option = self.expectation_tracker.borrow_mut().next();
-
-        if let Some((index, expectation)) = expectation_option {
-            if self.index == index {
-                if let Expectation::OutputPin(OutputPinExpectation::SetLow) = expectation {
-                    Ok(())
-                } else {
-                    panic!(
-                        "Did not expect call to set_low, expected: {:?}",
-                        expectation
-                    );
+    fn receive(&mut self) -> nb::Result<Self::Frame, Self::Error> {
+        // Polling an empty receive FIFO is not an expectation of its own
+        let expectation = self
+            .expectation_tracker
+            .with(|tracker| tracker.try_verify_call(self.index, &Call::Receive));
+
+        match expectation {
+            Some(Expectation::Can(CanExpectation::Received(frame))) => Ok(frame),
+            Some(Expectation::Can(CanExpectation::ReceiveError(error))) => {
+                Err(nb::Error::Other(error))
+            }
+            _ => Err(nb::Error::WouldBlock),
+        }
+    }
+}
+
+impl<H: TrackerHandle> CountDown for Mock<H> {
+    type Time = Duration;
+
+    fn start<T: Into<Self::Time>>(&mut self, count: T) {
+        let period = count.into();
+
+        self.verify(Call::StartTimer(period));
+        self.expectation_tracker
+            .with(|tracker| tracker.start_timer(self.index, period));
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        let expired = self.expectation_tracker.with(|tracker| {
+            // Waiting for the deadline is not an expectation of its own, only scripted expiries are
+            match tracker.try_verify_call(self.index, &Call::Wait) {
+                Some(_) => {
+                    tracker.expire_timer(self.index);
+                    true
                 }
-            } else {
-                panic!(
-                    "Mock with index {} cannot verify expectation with index {}",
-                    self.index, index
-                );
+                None => match tracker.poll_timer(self.index) {
+                    Some(expired) => expired,
+                    None => {
+                        // A timer that was never started would block forever without a diagnostic
+                        tracker.verify_call(self.index, &Call::Wait);
+                        false
+                    }
+                },
             }
+        });
+
+        if expired {
+            Ok(())
         } else {
-            panic!("Did not expect call to set_low, nothing was expected")
+            Err(nb::Error::WouldBlock)
         }
     }
 }
 
-impl StatefulOutputPin for Mock {
-    fn is_set_high(&self) -> Result<bool, Self::Error> {
-        Ok(true)
+impl<H: TrackerHandle> Periodic for Mock<H> {}
+
+impl<H: TrackerHandle> PwmPin for Mock<H> {
+    type Duty = u16;
+
+    fn disable(&mut self) {
+        self.verify(Call::Disable);
     }
 
-    fn is_set_low(&self) -> Result<bool, Self::Error> {
-        Ok(true)
+    fn enable(&mut self) {
+        self.verify(Call::Enable);
+    }
+
+    fn get_duty(&self) -> Self::Duty {
+        self.expectation_tracker.with(|tracker| {
+            // Reading back the duty cycle is only verified if an answer is expected
+            match tracker.try_verify_call(self.index, &Call::GetDuty) {
+                Some(Expectation::Pwm(PwmExpectation::GetDuty(duty))) => duty,
+                _ => tracker
+                    .duty_history(self.index)
+                    .last()
+                    .copied()
+                    .unwrap_or(0),
+            }
+        })
+    }
+
+    fn get_max_duty(&self) -> Self::Duty {
+        self.expectation_tracker.with(|tracker| {
+            if let Some(Expectation::Pwm(PwmExpectation::GetMaxDuty(max_duty))) =
+                tracker.try_verify_call(self.index, &Call::GetMaxDuty)
+            {
+                tracker.set_max_duty(self.index, max_duty);
+            }
+
+            tracker.max_duty(self.index)
+        })
+    }
+
+    fn set_duty(&mut self, duty: Self::Duty) {
+        self.verify(Call::SetDuty(duty));
+        self.expectation_tracker
+            .with(|tracker| tracker.push_duty(self.index, duty));
     }
 }
+
+macro_rules! impl_delay {
+    ($($word:ty),*) => {
+        $(
+            impl<H: TrackerHandle> DelayMs<$word> for Mock<H> {
+                fn delay_ms(&mut self, ms: $word) {
+                    self.delay(Duration::from_millis(ms as u64));
+                }
+            }
+
+            impl<H: TrackerHandle> DelayUs<$word> for Mock<H> {
+                fn delay_us(&mut self, us: $word) {
+                    self.delay(Duration::from_micros(us as u64));
+                }
+            }
+        )*
+    };
+}
+
+impl_delay!(u8, u16, u32, u64);
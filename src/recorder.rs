@@ -0,0 +1,192 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use ross_protocol::interface::{Interface, InterfaceError};
+use ross_protocol::packet::Packet;
+
+use crate::{
+    Expectation, ExpectationTracker, InterfaceExpectation, Mock, MockInterfaceError, Times,
+    TrackerHandle,
+};
+
+/// Interface wrapper logging all traffic of the wrapped interface
+///
+/// Panics on interface errors without a `MockInterfaceError` equivalent, as they cannot be replayed.
+#[derive(Debug)]
+pub struct Recorder<I: Interface> {
+    interface: I,
+    log: Vec<InterfaceExpectation>,
+}
+
+impl<I: Interface> Recorder<I> {
+    pub fn new(interface: I) -> Self {
+        Self {
+            interface,
+            log: vec![],
+        }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.interface
+    }
+
+    /// Recorded traffic in the order the calls were made
+    pub fn log(&self) -> &[InterfaceExpectation] {
+        &self.log
+    }
+
+    /// Recorded traffic with consecutive identical calls merged into one repeated expectation
+    pub fn script(&self) -> Vec<(InterfaceExpectation, Times)> {
+        let mut script: Vec<(InterfaceExpectation, Times)> = vec![];
+
+        for expectation in self.log.iter() {
+            if let Some((last_expectation, Times::Exactly(times))) = script.last_mut() {
+                if last_expectation == expectation {
+                    *times += 1;
+                    continue;
+                }
+            }
+
+            script.push((expectation.clone(), Times::Exactly(1)));
+        }
+
+        script
+    }
+
+    /// Expects the recorded traffic to be repeated by the given mock
+    pub fn replay<H: TrackerHandle>(&self, tracker: H, mock: &Mock<H>) {
+        for (expectation, times) in self.script() {
+            ExpectationTracker::expect_times(
+                tracker.clone(),
+                mock,
+                Expectation::Interface(expectation),
+                times,
+            );
+        }
+    }
+}
+
+impl<I: Interface> Interface for Recorder<I> {
+    fn try_get_packet(&mut self) -> Result<Packet, InterfaceError> {
+        let result = self.interface.try_get_packet();
+
+        match result {
+            Ok(ref packet) => self
+                .log
+                .push(InterfaceExpectation::ReceivedPacket(packet.clone())),
+            Err(ref err) => self
+                .log
+                .push(InterfaceExpectation::ReceiveError(recordable_error(err))),
+        }
+
+        result
+    }
+
+    fn try_send_packet(&mut self, packet: &Packet) -> Result<(), InterfaceError> {
+        let result = self.interface.try_send_packet(packet);
+
+        match result {
+            Ok(()) => self
+                .log
+                .push(InterfaceExpectation::SentPacket(packet.clone())),
+            Err(ref err) => self
+                .log
+                .push(InterfaceExpectation::SendError(recordable_error(err))),
+        }
+
+        result
+    }
+}
+
+/// Leaving the call out of the log would desynchronize the replay, so unmappable errors panic
+fn recordable_error(err: &InterfaceError) -> MockInterfaceError {
+    match MockInterfaceError::from_interface_error(err) {
+        Some(error) => error,
+        None => panic!(
+            "Cannot record interface error {:?}, it has no MockInterfaceError equivalent",
+            err
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::VirtualBus;
+
+    fn session<I: Interface>(interface: &mut I) {
+        let _ = interface.try_get_packet();
+        let _ = interface.try_get_packet();
+
+        interface
+            .try_send_packet(&Packet {
+                is_error: false,
+                device_address: 0x1111,
+                data: vec![0x11, 0x11, 0x11],
+            })
+            .unwrap();
+
+        let _ = interface.try_get_packet();
+    }
+
+    #[test]
+    fn record_and_replay_test() {
+        let bus = VirtualBus::new();
+        let mut device = Recorder::new(VirtualBus::endpoint(bus.clone()));
+        let mut peer = VirtualBus::endpoint(bus.clone());
+
+        session(&mut device);
+
+        peer.try_send_packet(&Packet {
+            is_error: false,
+            device_address: 0x2222,
+            data: vec![0x22],
+        })
+        .unwrap();
+
+        let _ = device.try_get_packet();
+
+        assert_eq!(device.log().len(), 5);
+        assert_eq!(
+            device.script(),
+            vec![
+                (
+                    InterfaceExpectation::ReceiveError(MockInterfaceError::NoPacketReceived),
+                    Times::Exactly(2),
+                ),
+                (
+                    InterfaceExpectation::SentPacket(Packet {
+                        is_error: false,
+                        device_address: 0x1111,
+                        data: vec![0x11, 0x11, 0x11],
+                    }),
+                    Times::Exactly(1),
+                ),
+                (
+                    InterfaceExpectation::ReceiveError(MockInterfaceError::NoPacketReceived),
+                    Times::Exactly(1),
+                ),
+                (
+                    InterfaceExpectation::ReceivedPacket(Packet {
+                        is_error: false,
+                        device_address: 0x2222,
+                        data: vec![0x22],
+                    }),
+                    Times::Exactly(1),
+                ),
+            ]
+        );
+
+        let tracker = ExpectationTracker::new();
+        let mut mock = ExpectationTracker::mock(tracker.clone());
+
+        device.replay(tracker.clone(), &mock);
+
+        session(&mut mock);
+
+        assert_eq!(mock.try_get_packet().unwrap().device_address, 0x2222);
+
+        tracker.borrow_mut().done();
+    }
+}
@@ -0,0 +1,302 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use embedded_hal::digital::v2::PinState;
+
+use ross_protocol::packet::Packet;
+
+use crate::{Call, CanExpectation, Expectation, InterfaceExpectation, PacketMatcher, Times};
+
+/// Writes bytes as a list of hex values
+pub(crate) fn fmt_bytes(f: &mut Formatter<'_>, bytes: &[u8]) -> FmtResult {
+    write!(f, "[")?;
+
+    for (i, byte) in bytes.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+
+        write!(f, "0x{:02x}", byte)?;
+    }
+
+    write!(f, "]")
+}
+
+/// Writes a packet field by field with hex encoded data
+pub(crate) fn fmt_packet(f: &mut Formatter<'_>, packet: &Packet) -> FmtResult {
+    write!(
+        f,
+        "is_error: {}, device_address: 0x{:04x}, data: ",
+        packet.is_error, packet.device_address
+    )?;
+
+    fmt_bytes(f, &packet.data)
+}
+
+/// Writes matcher descriptions separated by commas
+pub(crate) fn fmt_matchers(f: &mut Formatter<'_>, matchers: &[PacketMatcher]) -> FmtResult {
+    for (i, matcher) in matchers.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+
+        write!(f, "{}", matcher)?;
+    }
+
+    Ok(())
+}
+
+impl Display for Expectation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Expectation::Interface(InterfaceExpectation::SentPacket(packet)) => {
+                write!(f, "SentPacket(")?;
+                fmt_packet(f, packet)?;
+                write!(f, ")")
+            }
+            Expectation::Interface(InterfaceExpectation::ReceivedPacket(packet)) => {
+                write!(f, "ReceivedPacket(")?;
+                fmt_packet(f, packet)?;
+                write!(f, ")")
+            }
+            Expectation::Interface(InterfaceExpectation::SentEvent(event)) => {
+                write!(f, "SentEvent({})", event)
+            }
+            Expectation::Interface(InterfaceExpectation::ReceivedEvent(event)) => {
+                write!(f, "ReceivedEvent({})", event)
+            }
+            Expectation::Interface(InterfaceExpectation::SentPacketMatching(matchers)) => {
+                write!(f, "SentPacketMatching(")?;
+                fmt_matchers(f, matchers)?;
+                write!(f, ")")
+            }
+            Expectation::Can(CanExpectation::Transmitted(frame)) => {
+                write!(f, "Transmitted({})", frame)
+            }
+            Expectation::Can(CanExpectation::Received(frame)) => write!(f, "Received({})", frame),
+            Expectation::Can(CanExpectation::MailboxFull(frame)) => {
+                write!(f, "MailboxFull({})", frame)
+            }
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl Display for Call {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Call::TrySendPacket(packet) => {
+                write!(f, "{}(", self.name())?;
+                fmt_packet(f, packet)?;
+                write!(f, ")")
+            }
+            Call::Transmit(frame) => write!(f, "{}({})", self.name(), frame),
+            Call::Delay(duration) => write!(f, "{}({:?})", self.name(), duration),
+            Call::StartTimer(period) => write!(f, "{}({:?})", self.name(), period),
+            Call::SetDuty(duty) => write!(f, "{}({})", self.name(), duty),
+            _ => write!(f, "{}()", self.name()),
+        }
+    }
+}
+
+impl Display for Times {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Times::Exactly(times) => write!(f, "exactly {}", times),
+            Times::AtLeast(times) => write!(f, "at least {}", times),
+            Times::AtMost(times) => write!(f, "at most {}", times),
+            Times::Any => write!(f, "any number of"),
+        }
+    }
+}
+
+/// Identifies a mock in diagnostics
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockId {
+    pub index: usize,
+    pub name: Option<String>,
+}
+
+impl Display for MockId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.name {
+            Some(name) => write!(f, "\"{}\" with index {}", name, self.index),
+            None => write!(f, "with index {}", self.index),
+        }
+    }
+}
+
+/// Reason a call did not match the expectations
+#[derive(Debug, Clone, PartialEq)]
+pub enum MismatchReason {
+    /// The next expectation belongs to a different mock
+    WrongMock(MockId, Expectation),
+    /// The next expectation does not accept the call
+    Unexpected(Expectation),
+    /// None of the pending expectations of the calling mock accept the call
+    UnexpectedAnyOf(Vec<Expectation>),
+    /// There were no pending expectations
+    NothingExpected,
+    /// The call was expected, but left the output pin at a different level
+    WrongState(Expectation, PinState),
+}
+
+/// A call that did not match the expectations
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// Position of the call among all calls verified by the tracker
+    pub position: usize,
+    /// The mock that was called
+    pub mock: MockId,
+    /// The call, including its arguments
+    pub call: Call,
+    pub reason: MismatchReason,
+}
+
+impl Mismatch {
+    /// The expectation the call was compared against, if there was exactly one
+    pub fn expected(&self) -> Option<&Expectation> {
+        match &self.reason {
+            MismatchReason::WrongMock(_, expectation) => Some(expectation),
+            MismatchReason::Unexpected(expectation) => Some(expectation),
+            MismatchReason::UnexpectedAnyOf(_) => None,
+            MismatchReason::NothingExpected => None,
+            MismatchReason::WrongState(expectation, _) => Some(expectation),
+        }
+    }
+}
+
+impl Mismatch {
+    fn fmt_call(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "Did not expect call to {}", self.call.name())?;
+
+        if let Some(name) = &self.mock.name {
+            write!(f, " on \"{}\"", name)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.reason {
+            MismatchReason::WrongMock(mock, _) => {
+                write!(f, "Mock {} cannot verify expectation {}", self.mock, mock)
+            }
+            MismatchReason::Unexpected(expectation) => {
+                self.fmt_call(f)?;
+                write!(f, ", expected: {:?}", expectation)?;
+
+                match (&self.call, expectation) {
+                    (
+                        Call::TrySendPacket(packet),
+                        Expectation::Interface(InterfaceExpectation::SentPacketMatching(matchers)),
+                    ) => {
+                        let failed: Vec<PacketMatcher> = matchers
+                            .iter()
+                            .filter(|matcher| !matcher.matches(packet))
+                            .cloned()
+                            .collect();
+
+                        write!(f, ", sent: ")?;
+                        fmt_packet(f, packet)?;
+                        write!(f, ", failed matchers: ")?;
+                        fmt_matchers(f, &failed)?;
+                    }
+                    (
+                        Call::TrySendPacket(packet),
+                        Expectation::Interface(InterfaceExpectation::SentEvent(event)),
+                    ) => {
+                        write!(f, ", sent: ")?;
+                        event.fmt_decoded(f, packet)?;
+                    }
+                    _ => {}
+                }
+
+                Ok(())
+            }
+            MismatchReason::UnexpectedAnyOf(expectations) => {
+                self.fmt_call(f)?;
+                write!(f, ", expected one of: {:?}", expectations)
+            }
+            MismatchReason::NothingExpected => {
+                self.fmt_call(f)?;
+                write!(f, ", nothing was expected")
+            }
+            MismatchReason::WrongState(expectation, PinState::High) => write!(
+                f,
+                "Output pin {} is set high after call to {}, expected: {:?}",
+                self.mock,
+                self.call.name(),
+                expectation
+            ),
+            MismatchReason::WrongState(expectation, PinState::Low) => write!(
+                f,
+                "Output pin {} is set low after call to {}, expected: {:?}",
+                self.mock,
+                self.call.name(),
+                expectation
+            ),
+        }
+    }
+}
+
+/// An expectation that did not receive enough calls
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingExpectation {
+    /// Position of the expectation in the order it was added
+    pub position: usize,
+    pub mock: MockId,
+    pub expectation: Expectation,
+    pub times: Times,
+    pub calls: usize,
+}
+
+/// All verification failures collected by the tracker
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationReport {
+    pub mismatches: Vec<Mismatch>,
+    pub pending: Vec<PendingExpectation>,
+}
+
+impl Display for VerificationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        writeln!(
+            f,
+            "Verification failed with {} pending expectations and {} mismatching calls",
+            self.pending.len(),
+            self.mismatches.len()
+        )?;
+
+        if !self.pending.is_empty() {
+            writeln!(f, "Pending expectations:")?;
+        }
+
+        for pending in self.pending.iter() {
+            writeln!(
+                f,
+                "  #{} mock {}: {} (expected {} calls, received {})",
+                pending.position, pending.mock, pending.expectation, pending.times, pending.calls
+            )?;
+        }
+
+        if !self.mismatches.is_empty() {
+            writeln!(f, "Mismatching calls:")?;
+        }
+
+        for mismatch in self.mismatches.iter() {
+            if mismatch.reason == MismatchReason::NothingExpected {
+                writeln!(
+                    f,
+                    "  call {}: unexpected extra call to {} on mock {}, no expectations were left",
+                    mismatch.position, mismatch.call, mismatch.mock
+                )?;
+            } else {
+                writeln!(f, "  call {}: {}", mismatch.position, mismatch)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+/// Declares named mocks and their ordered expectations, runs the body and verifies the tracker
+///
+/// Every step is written as `mock: operation` with the names of the `ExpectationBuilder` methods,
+/// packets as `send(address, [data])` or `send(address, [data], error)` and interface errors by
+/// their `MockInterfaceError` name. A step can end with `times N`, `at_least N`, `at_most N` or
+/// `any`. Steps are verified in strict order across all mocks.
+///
+/// The scenario can start with `tracker { name }` to make the tracker reachable from the body,
+/// for example to advance its virtual clock.
+///
+/// ```
+/// use embedded_hal::digital::v2::{InputPin, OutputPin};
+/// use ross_mock::scenario;
+/// use ross_protocol::interface::Interface;
+/// use ross_protocol::packet::Packet;
+///
+/// scenario! {
+///     mocks { button, relay, bus }
+///     expect {
+///         bus: receive_error(NoPacketReceived) times 2;
+///         button: is_high;
+///         relay: set_low;
+///         bus: send(0x1111, [0x01, 0x02]);
+///     }
+///     run {
+///         assert!(bus.try_get_packet().is_err());
+///         assert!(bus.try_get_packet().is_err());
+///
+///         if button.is_high().unwrap() {
+///             relay.set_low().unwrap();
+///             bus.try_send_packet(&Packet {
+///                 is_error: false,
+///                 device_address: 0x1111,
+///                 data: vec![0x01, 0x02],
+///             })
+///             .unwrap();
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! scenario {
+    (
+        tracker { $tracker:ident }
+        mocks { $($mock:ident),* $(,)? }
+        expect {
+            $(
+                $step_mock:ident : $operation:ident $( ( $($argument:tt)* ) )?
+                    $( $modifier:ident $($count:literal)? )? ;
+            )*
+        }
+        run { $($body:tt)* }
+    ) => {{
+        let $tracker = $crate::ExpectationTracker::with_order($crate::ExpectationOrder::Strict);
+
+        $(
+            #[allow(unused_mut)]
+            let mut $mock =
+                $crate::ExpectationTracker::named_mock($tracker.clone(), stringify!($mock));
+        )*
+
+        $(
+            let handle = $crate::scenario!(
+                @operation $step_mock, $operation ( $( $($argument)* )? )
+            );
+            $( $crate::scenario!(@times handle, $modifier $($count)?); )?
+            let _ = handle;
+        )*
+
+        { $($body)* }
+
+        $tracker.borrow_mut().done();
+    }};
+    (mocks $($rest:tt)*) => {
+        $crate::scenario! { tracker { tracker } mocks $($rest)* }
+    };
+
+    (@operation $mock:ident, send ($address:expr, [$($byte:expr),* $(,)?] $(, $flag:ident)?)) => {
+        $mock.expect().send($crate::scenario!(@packet $address, [$($byte),*] $(, $flag)?))
+    };
+    (@operation $mock:ident, receive ($address:expr, [$($byte:expr),* $(,)?] $(, $flag:ident)?)) => {
+        $mock.expect().receive($crate::scenario!(@packet $address, [$($byte),*] $(, $flag)?))
+    };
+    (@operation $mock:ident, send_error ($error:ident)) => {
+        $mock.expect().send_error($crate::MockInterfaceError::$error)
+    };
+    (@operation $mock:ident, receive_error ($error:ident)) => {
+        $mock.expect().receive_error($crate::MockInterfaceError::$error)
+    };
+    (@operation $mock:ident, toggle_to (high)) => {
+        $mock.expect().toggle_to($crate::embedded_hal::digital::v2::PinState::High)
+    };
+    (@operation $mock:ident, toggle_to (low)) => {
+        $mock.expect().toggle_to($crate::embedded_hal::digital::v2::PinState::Low)
+    };
+    (@operation $mock:ident, $operation:ident ($($argument:expr),*)) => {
+        $mock.expect().$operation($($argument),*)
+    };
+
+    (@packet $address:expr, [$($byte:expr),*]) => {
+        $crate::scenario!(@packet $address, [$($byte),*], false)
+    };
+    (@packet $address:expr, [$($byte:expr),*], error) => {
+        $crate::scenario!(@packet $address, [$($byte),*], true)
+    };
+    (@packet $address:expr, [$($byte:expr),*], $is_error:literal) => {
+        $crate::ross_protocol::packet::Packet {
+            is_error: $is_error,
+            device_address: $address,
+            data: <[u8]>::to_vec(&[$($byte),*]),
+        }
+    };
+
+    (@times $handle:ident, times $count:literal) => {
+        let $handle = $handle.times($count);
+    };
+    (@times $handle:ident, at_least $count:literal) => {
+        let $handle = $handle.at_least($count);
+    };
+    (@times $handle:ident, at_most $count:literal) => {
+        let $handle = $handle.at_most($count);
+    };
+    (@times $handle:ident, any) => {
+        let $handle = $handle.any_times();
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use core::time::Duration;
+    use embedded_hal::digital::v2::{InputPin, OutputPin, ToggleableOutputPin};
+    use embedded_hal::timer::CountDown;
+    use ross_protocol::interface::Interface;
+    use ross_protocol::packet::Packet;
+
+    #[test]
+    fn scenario_test() {
+        scenario! {
+            mocks { button, relay, bus }
+            expect {
+                bus: receive(0x2222, [0x03]);
+                button: is_high at_least 1;
+                relay: set_low;
+                relay: toggle_to(high);
+                relay: toggle any;
+                bus: send(0x1111, [0x01, 0x02]);
+            }
+            run {
+                assert_eq!(bus.try_get_packet().unwrap().data, vec![0x03]);
+                assert!(button.is_high().unwrap());
+                assert!(button.is_high().unwrap());
+                relay.set_low().unwrap();
+                relay.toggle().unwrap();
+                relay.toggle().unwrap();
+                relay.toggle().unwrap();
+                bus.try_send_packet(&Packet {
+                    is_error: false,
+                    device_address: 0x1111,
+                    data: vec![0x01, 0x02],
+                })
+                .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Verification failed with 1 pending expectations")]
+    fn scenario_verifies_test() {
+        scenario! {
+            mocks { relay }
+            expect {
+                relay: set_high;
+                relay: set_low;
+            }
+            run {
+                relay.set_high().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Mock \"relay\" with index 1 cannot verify expectation \"button\" with index 0"
+    )]
+    fn scenario_order_test() {
+        scenario! {
+            mocks { button, relay }
+            expect {
+                button: is_high;
+                relay: set_low;
+            }
+            run {
+                relay.set_low().unwrap();
+                assert!(button.is_high().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn scenario_error_packet_test() {
+        scenario! {
+            mocks { bus }
+            expect {
+                bus: receive(0x2222, [0x03], error);
+                bus: send(0x1111, [0x01]);
+            }
+            run {
+                assert!(bus.try_get_packet().unwrap().is_error);
+                bus.try_send_packet(&Packet {
+                    is_error: false,
+                    device_address: 0x1111,
+                    data: vec![0x01],
+                })
+                .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn scenario_tracker_test() {
+        scenario! {
+            tracker { clock }
+            mocks { timer }
+            expect {
+                timer: start(Duration::from_millis(10));
+            }
+            run {
+                timer.start(Duration::from_millis(10));
+                assert!(timer.wait().is_err());
+
+                clock.borrow_mut().advance(Duration::from_millis(10));
+                assert!(timer.wait().is_ok());
+            }
+        }
+    }
+}
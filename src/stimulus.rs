@@ -0,0 +1,270 @@
+use core::ops::RangeInclusive;
+use core::time::Duration;
+use embedded_hal::digital::v2::PinState;
+
+use crate::Waveform;
+
+/// Contact bounce after every edge, as the given number of short glitches back to the previous level
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bounce {
+    pub count: RangeInclusive<usize>,
+    /// Duration of every glitch and of every return to the new level in between
+    pub duration: RangeInclusive<Duration>,
+}
+
+/// Durations used by the button stimulus helpers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ButtonTiming {
+    pub press: Duration,
+    pub long_press: Duration,
+    /// Time released between the two presses of a double click
+    pub double_click_gap: Duration,
+}
+
+impl Default for ButtonTiming {
+    fn default() -> Self {
+        Self {
+            press: Duration::from_millis(100),
+            long_press: Duration::from_millis(1500),
+            double_click_gap: Duration::from_millis(150),
+        }
+    }
+}
+
+/// Xorshift generator, so noisy signals are reproducible from their seed
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck at zero
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn range(&mut self, min: u64, max: u64) -> u64 {
+        if max <= min {
+            return min;
+        }
+
+        min + self.next() % (max - min + 1)
+    }
+}
+
+/// Builds button waveforms for `Mock::drive`, starting and ending released
+#[derive(Debug, Clone)]
+pub struct ButtonStimulus {
+    pressed_level: PinState,
+    timing: ButtonTiming,
+    bounce: Option<Bounce>,
+    rng: Rng,
+    waveform: Waveform,
+}
+
+impl ButtonStimulus {
+    /// Creates a stimulus for a button that reads the given level while pressed
+    pub fn new(pressed_level: PinState) -> Self {
+        Self {
+            pressed_level,
+            timing: ButtonTiming::default(),
+            bounce: None,
+            rng: Rng::new(1),
+            waveform: Waveform::new(!pressed_level),
+        }
+    }
+
+    pub fn with_timing(mut self, timing: ButtonTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Adds bounce to every following edge, randomized from the seed
+    pub fn with_bounce(mut self, bounce: Bounce, seed: u64) -> Self {
+        self.bounce = Some(bounce);
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    /// Keeps the button released
+    pub fn idle(mut self, duration: Duration) -> Self {
+        self.waveform = self.waveform.segment(!self.pressed_level, duration);
+        self
+    }
+
+    /// Holds the button pressed for the given duration and releases it
+    pub fn hold(self, duration: Duration) -> Self {
+        let pressed_level = self.pressed_level;
+
+        // The zero length segment holds the released level once the waveform is over
+        self.edge(pressed_level)
+            .level(pressed_level, duration)
+            .edge(!pressed_level)
+            .level(!pressed_level, Duration::ZERO)
+    }
+
+    pub fn press(self) -> Self {
+        let press = self.timing.press;
+        self.hold(press)
+    }
+
+    pub fn long_press(self) -> Self {
+        let long_press = self.timing.long_press;
+        self.hold(long_press)
+    }
+
+    pub fn double_click(self) -> Self {
+        let double_click_gap = self.timing.double_click_gap;
+        self.press().idle(double_click_gap).press()
+    }
+
+    pub fn build(self) -> Waveform {
+        self.waveform
+    }
+
+    fn level(mut self, level: PinState, duration: Duration) -> Self {
+        self.waveform = self.waveform.segment(level, duration);
+        self
+    }
+
+    fn edge(mut self, level: PinState) -> Self {
+        let bounce = match self.bounce.clone() {
+            Some(bounce) => bounce,
+            None => return self,
+        };
+
+        let count = self
+            .rng
+            .range(*bounce.count.start() as u64, *bounce.count.end() as u64);
+
+        for _ in 0..count {
+            for glitch_level in [level, !level] {
+                let duration = Duration::from_micros(self.rng.range(
+                    bounce.duration.start().as_micros() as u64,
+                    bounce.duration.end().as_micros() as u64,
+                ));
+
+                self = self.level(glitch_level, duration);
+            }
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::WaveformSegment;
+
+    fn segment(level: PinState, millis: u64) -> WaveformSegment {
+        WaveformSegment {
+            level,
+            duration: Duration::from_millis(millis),
+        }
+    }
+
+    fn bounce() -> Bounce {
+        Bounce {
+            count: 2..=5,
+            duration: Duration::from_micros(50)..=Duration::from_micros(800),
+        }
+    }
+
+    #[test]
+    fn clean_press_test() {
+        let waveform = ButtonStimulus::new(PinState::Low)
+            .idle(Duration::from_millis(10))
+            .press()
+            .idle(Duration::from_millis(10))
+            .long_press()
+            .build();
+
+        assert_eq!(
+            &waveform.segments()[1..],
+            &[
+                segment(PinState::High, 10),
+                segment(PinState::Low, 100),
+                segment(PinState::High, 0),
+                segment(PinState::High, 10),
+                segment(PinState::Low, 1500),
+                segment(PinState::High, 0),
+            ]
+        );
+        assert_eq!(waveform.level_at(Duration::from_secs(10)), PinState::High);
+    }
+
+    #[test]
+    fn double_click_test() {
+        let waveform = ButtonStimulus::new(PinState::High)
+            .with_timing(ButtonTiming {
+                press: Duration::from_millis(50),
+                double_click_gap: Duration::from_millis(80),
+                ..ButtonTiming::default()
+            })
+            .double_click()
+            .build();
+
+        assert_eq!(
+            &waveform.segments()[1..],
+            &[
+                segment(PinState::High, 50),
+                segment(PinState::Low, 0),
+                segment(PinState::Low, 80),
+                segment(PinState::High, 50),
+                segment(PinState::Low, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn bounce_test() {
+        let waveform = ButtonStimulus::new(PinState::Low)
+            .with_bounce(bounce(), 42)
+            .hold(Duration::from_millis(200))
+            .build();
+
+        let segments = &waveform.segments()[1..];
+        let hold = segments
+            .iter()
+            .position(|segment| segment.duration == Duration::from_millis(200))
+            .unwrap();
+
+        // Glitches come in pairs ending at the new level, before and after the stable press
+        assert!((4..=10).contains(&hold) && hold % 2 == 0);
+        assert!(segments.len() - hold - 2 >= 4);
+
+        for (i, segment) in segments[..hold].iter().enumerate() {
+            assert_eq!(
+                segment.level,
+                if i % 2 == 0 {
+                    PinState::Low
+                } else {
+                    PinState::High
+                }
+            );
+            assert!(bounce().duration.contains(&segment.duration));
+        }
+
+        assert_eq!(waveform.level_at(waveform.duration()), PinState::High);
+    }
+
+    #[test]
+    fn seeded_bounce_test() {
+        let build = |seed| {
+            ButtonStimulus::new(PinState::Low)
+                .with_bounce(bounce(), seed)
+                .press()
+                .build()
+        };
+
+        assert_eq!(build(7), build(7));
+        assert_ne!(build(7), build(8));
+    }
+}
@@ -0,0 +1,839 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use core::time::Duration;
+use embedded_hal::digital::v2::PinState;
+
+use ross_protocol::packet::Packet;
+
+use crate::{
+    DelayExpectation, Expectation, ExpectationTracker, InputPinExpectation, InterfaceExpectation,
+    MockInterfaceError, OutputPinExpectation, PwmExpectation, TimerExpectation, Times,
+    TrackerHandle,
+};
+
+const INTERFACE_ERRORS: [(&str, MockInterfaceError); 15] = [
+    ("NoPacketReceived", MockInterfaceError::NoPacketReceived),
+    ("CanBufferOverrun", MockInterfaceError::CanBufferOverrun),
+    ("CanMailboxFull", MockInterfaceError::CanMailboxFull),
+    ("UsartReadError", MockInterfaceError::UsartReadError),
+    ("BuilderOutOfOrder", MockInterfaceError::BuilderOutOfOrder),
+    (
+        "BuilderSingleFramePacket",
+        MockInterfaceError::BuilderSingleFramePacket,
+    ),
+    (
+        "BuilderTooManyFrames",
+        MockInterfaceError::BuilderTooManyFrames,
+    ),
+    (
+        "BuilderWrongFrameType",
+        MockInterfaceError::BuilderWrongFrameType,
+    ),
+    (
+        "BuilderDeviceAddressMismatch",
+        MockInterfaceError::BuilderDeviceAddressMismatch,
+    ),
+    (
+        "BuilderMissingFrames",
+        MockInterfaceError::BuilderMissingFrames,
+    ),
+    ("FrameIsStandard", MockInterfaceError::FrameIsStandard),
+    ("FrameIsRemote", MockInterfaceError::FrameIsRemote),
+    ("FrameIdMissing", MockInterfaceError::FrameIdMissing),
+    ("FrameWrongSize", MockInterfaceError::FrameWrongSize),
+    ("FrameCobsError", MockInterfaceError::FrameCobsError),
+];
+
+/// Failure to parse, serialize or load a transcript
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscriptError {
+    /// The line with the given number, counted from 1, could not be parsed
+    Syntax(usize, String),
+    /// The tracker has no mock with the given name
+    UnknownMock(String),
+    /// The mock name is empty, contains whitespace or starts a comment, so it cannot be parsed back
+    InvalidMockName(String),
+    /// The expectation at the given position belongs to a mock without a name
+    UnnamedMock(usize),
+    /// The expectation at the given position has no text representation
+    Unsupported(usize),
+}
+
+impl Display for TranscriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            TranscriptError::Syntax(line, message) => write!(f, "line {}: {}", line, message),
+            TranscriptError::UnknownMock(name) => write!(f, "no mock named \"{}\"", name),
+            TranscriptError::InvalidMockName(name) => {
+                write!(f, "mock name \"{}\" cannot be written as text", name)
+            }
+            TranscriptError::UnnamedMock(position) => {
+                write!(f, "expectation #{} belongs to an unnamed mock", position)
+            }
+            TranscriptError::Unsupported(position) => {
+                write!(f, "expectation #{} cannot be written as text", position)
+            }
+        }
+    }
+}
+
+/// A single expectation of a transcript
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptLine {
+    pub mock: String,
+    pub expectation: Expectation,
+    pub times: Times,
+}
+
+/// Expectation script in a line based text format
+///
+/// Every line holds one expectation as `<mock> <operation> [arguments] [times]`, for example:
+///
+/// ```text
+/// # comments and empty lines are ignored
+/// relay recv 0x0001 ok 01 02
+/// relay send 0x0001 err
+/// relay recv_error NoPacketReceived x3
+/// button is_high x2+
+/// led set_high
+/// led toggle_to low
+/// led delay 500ms
+/// led delay 10us..20us
+/// led delay 1500ns
+/// timer start 10ms
+/// timer expire x2
+/// dimmer set_duty 500
+/// dimmer set_duty 490..510
+/// dimmer get_max_duty 1000
+/// ```
+///
+/// Times are written as `xN` (exactly), `xN+` (at least), `x..N` (at most) or `x*` (any number of),
+/// a line without them is expected exactly once. Mocks are referred to by their names, which cannot
+/// contain whitespace or start with `#`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Transcript {
+    lines: Vec<TranscriptLine>,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self { lines: vec![] }
+    }
+
+    pub fn lines(&self) -> &[TranscriptLine] {
+        &self.lines
+    }
+
+    /// Appends an expectation, typed events are stored as their packets
+    pub fn push(
+        &mut self,
+        mock: &str,
+        expectation: Expectation,
+        times: Times,
+    ) -> Result<(), TranscriptError> {
+        if mock.is_empty() || mock.starts_with('#') || mock.contains(char::is_whitespace) {
+            return Err(TranscriptError::InvalidMockName(mock.to_string()));
+        }
+
+        let expectation =
+            representable(expectation).ok_or(TranscriptError::Unsupported(self.lines.len()))?;
+
+        self.lines.push(TranscriptLine {
+            mock: mock.to_string(),
+            expectation,
+            times,
+        });
+
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, TranscriptError> {
+        let mut transcript = Self::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let line =
+                parse_line(line).map_err(|message| TranscriptError::Syntax(i + 1, message))?;
+            transcript.lines.push(line);
+        }
+
+        Ok(transcript)
+    }
+
+    /// Captures all expectations added to the tracker, their mocks must be named
+    pub fn from_tracker(tracker: &ExpectationTracker) -> Result<Self, TranscriptError> {
+        let mut transcript = Self::new();
+
+        for (position, (mock_index, expectation, times)) in tracker.entries().enumerate() {
+            let name = tracker
+                .mock_id(mock_index)
+                .name
+                .ok_or(TranscriptError::UnnamedMock(position))?;
+
+            transcript.push(&name, expectation.clone(), times)?;
+        }
+
+        Ok(transcript)
+    }
+
+    /// Adds all expectations to the tracker, using the mocks with matching names
+    pub fn load<H: TrackerHandle>(&self, tracker: H) -> Result<(), TranscriptError> {
+        let mut mocks = vec![];
+
+        for line in self.lines.iter() {
+            match ExpectationTracker::find_mock(tracker.clone(), &line.mock) {
+                Some(mock) => mocks.push(mock),
+                None => return Err(TranscriptError::UnknownMock(line.mock.clone())),
+            }
+        }
+
+        for (line, mock) in self.lines.iter().zip(mocks.iter()) {
+            ExpectationTracker::expect_times(
+                tracker.clone(),
+                mock,
+                line.expectation.clone(),
+                line.times,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Transcript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for line in self.lines.iter() {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for TranscriptLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} ", self.mock)?;
+
+        match &self.expectation {
+            Expectation::Interface(InterfaceExpectation::SentPacket(packet)) => {
+                write!(f, "send ")?;
+                fmt_packet(f, packet)?;
+            }
+            Expectation::Interface(InterfaceExpectation::ReceivedPacket(packet)) => {
+                write!(f, "recv ")?;
+                fmt_packet(f, packet)?;
+            }
+            Expectation::Interface(InterfaceExpectation::SentEvent(event)) => {
+                write!(f, "send ")?;
+                fmt_packet(f, event.packet())?;
+            }
+            Expectation::Interface(InterfaceExpectation::ReceivedEvent(event)) => {
+                write!(f, "recv ")?;
+                fmt_packet(f, event.packet())?;
+            }
+            Expectation::Interface(InterfaceExpectation::SendError(error)) => {
+                write!(f, "send_error {:?}", error)?;
+            }
+            Expectation::Interface(InterfaceExpectation::ReceiveError(error)) => {
+                write!(f, "recv_error {:?}", error)?;
+            }
+            // The next two are rejected by `Transcript::push`, written for diagnostics only
+            Expectation::Interface(InterfaceExpectation::SentPacketMatching(_)) => {
+                write!(f, "send_matching {}", self.expectation)?;
+            }
+            Expectation::Can(_) => write!(f, "can {}", self.expectation)?,
+            Expectation::Delay(DelayExpectation::Exactly(duration)) => {
+                write!(f, "delay ")?;
+                fmt_duration(f, *duration)?;
+            }
+            Expectation::Delay(DelayExpectation::AtLeast(min)) => {
+                write!(f, "delay ")?;
+                fmt_duration(f, *min)?;
+                write!(f, "..")?;
+            }
+            Expectation::Delay(DelayExpectation::AtMost(max)) => {
+                write!(f, "delay ..")?;
+                fmt_duration(f, *max)?;
+            }
+            Expectation::Delay(DelayExpectation::Between(min, max)) => {
+                write!(f, "delay ")?;
+                fmt_duration(f, *min)?;
+                write!(f, "..")?;
+                fmt_duration(f, *max)?;
+            }
+            Expectation::Timer(TimerExpectation::Started(period)) => {
+                write!(f, "start ")?;
+                fmt_duration(f, *period)?;
+            }
+            Expectation::Timer(TimerExpectation::Expired) => write!(f, "expire")?,
+            Expectation::Pwm(PwmExpectation::Enable) => write!(f, "enable")?,
+            Expectation::Pwm(PwmExpectation::Disable) => write!(f, "disable")?,
+            Expectation::Pwm(PwmExpectation::SetDuty(duty)) => write!(f, "set_duty {}", duty)?,
+            Expectation::Pwm(PwmExpectation::SetDutyBetween(min, max)) => {
+                write!(f, "set_duty {}..{}", min, max)?
+            }
+            Expectation::Pwm(PwmExpectation::GetDuty(duty)) => write!(f, "get_duty {}", duty)?,
+            Expectation::Pwm(PwmExpectation::GetMaxDuty(max_duty)) => {
+                write!(f, "get_max_duty {}", max_duty)?
+            }
+            Expectation::InputPin(InputPinExpectation::IsHigh) => write!(f, "is_high")?,
+            Expectation::InputPin(InputPinExpectation::IsLow) => write!(f, "is_low")?,
+            Expectation::OutputPin(OutputPinExpectation::SetHigh) => write!(f, "set_high")?,
+            Expectation::OutputPin(OutputPinExpectation::SetLow) => write!(f, "set_low")?,
+            Expectation::OutputPin(OutputPinExpectation::IsSetHigh) => write!(f, "is_set_high")?,
+            Expectation::OutputPin(OutputPinExpectation::IsSetLow) => write!(f, "is_set_low")?,
+            Expectation::OutputPin(OutputPinExpectation::Toggle) => write!(f, "toggle")?,
+            Expectation::OutputPin(OutputPinExpectation::ToggleTo(PinState::High)) => {
+                write!(f, "toggle_to high")?
+            }
+            Expectation::OutputPin(OutputPinExpectation::ToggleTo(PinState::Low)) => {
+                write!(f, "toggle_to low")?
+            }
+        }
+
+        match self.times {
+            Times::Exactly(1) => Ok(()),
+            Times::Exactly(times) => write!(f, " x{}", times),
+            Times::AtLeast(times) => write!(f, " x{}+", times),
+            Times::AtMost(times) => write!(f, " x..{}", times),
+            Times::Any => write!(f, " x*"),
+        }
+    }
+}
+
+/// Converts typed events to packets, matchers and CAN frames have no text representation
+fn representable(expectation: Expectation) -> Option<Expectation> {
+    match expectation {
+        Expectation::Interface(InterfaceExpectation::SentEvent(event)) => Some(
+            Expectation::Interface(InterfaceExpectation::SentPacket(event.packet().clone())),
+        ),
+        Expectation::Interface(InterfaceExpectation::ReceivedEvent(event)) => Some(
+            Expectation::Interface(InterfaceExpectation::ReceivedPacket(event.packet().clone())),
+        ),
+        Expectation::Interface(InterfaceExpectation::SentPacketMatching(_)) => None,
+        Expectation::Can(_) => None,
+        expectation => Some(expectation),
+    }
+}
+
+fn fmt_packet(f: &mut Formatter<'_>, packet: &Packet) -> FmtResult {
+    write!(
+        f,
+        "0x{:04x} {}",
+        packet.device_address,
+        if packet.is_error { "err" } else { "ok" }
+    )?;
+
+    for byte in packet.data.iter() {
+        write!(f, " {:02x}", byte)?;
+    }
+
+    Ok(())
+}
+
+/// Durations are written in microseconds unless that would drop nanoseconds
+fn fmt_duration(f: &mut Formatter<'_>, duration: Duration) -> FmtResult {
+    if duration.subsec_nanos().is_multiple_of(1000) {
+        write!(f, "{}us", duration.as_micros())
+    } else {
+        write!(f, "{}ns", duration.as_nanos())
+    }
+}
+
+fn parse_line(line: &str) -> Result<TranscriptLine, String> {
+    let mut tokens: Vec<&str> = line.split_whitespace().collect();
+
+    // Hex bytes never start with 'x', so a trailing token starting with it is always the times
+    let times = match tokens.last() {
+        Some(token) if tokens.len() > 2 && token.starts_with('x') => {
+            let times = parse_times(&token[1..])?;
+            tokens.pop();
+            times
+        }
+        _ => Times::Exactly(1),
+    };
+
+    let (mock, operation, arguments) = match tokens.as_slice() {
+        [mock, operation, arguments @ ..] => (*mock, *operation, arguments),
+        _ => return Err("expected a mock name and an operation".to_string()),
+    };
+
+    let expectation = match operation {
+        "send" => {
+            Expectation::Interface(InterfaceExpectation::SentPacket(parse_packet(arguments)?))
+        }
+        "recv" => Expectation::Interface(InterfaceExpectation::ReceivedPacket(parse_packet(
+            arguments,
+        )?)),
+        "send_error" => Expectation::Interface(InterfaceExpectation::SendError(
+            parse_interface_error(arguments)?,
+        )),
+        "recv_error" => Expectation::Interface(InterfaceExpectation::ReceiveError(
+            parse_interface_error(arguments)?,
+        )),
+        "delay" => match arguments {
+            [delay] => Expectation::Delay(parse_delay(delay)?),
+            _ => return Err("expected a duration after delay".to_string()),
+        },
+        "start" => match arguments {
+            [period] => Expectation::Timer(TimerExpectation::Started(parse_duration(period)?)),
+            _ => return Err("expected a period after start".to_string()),
+        },
+        "set_duty" => match arguments {
+            [duty] => match duty.split_once("..") {
+                Some((min, max)) => Expectation::Pwm(PwmExpectation::SetDutyBetween(
+                    parse_duty(min)?,
+                    parse_duty(max)?,
+                )),
+                None => Expectation::Pwm(PwmExpectation::SetDuty(parse_duty(duty)?)),
+            },
+            _ => return Err("expected a duty cycle after set_duty".to_string()),
+        },
+        "get_duty" => match arguments {
+            [duty] => Expectation::Pwm(PwmExpectation::GetDuty(parse_duty(duty)?)),
+            _ => return Err("expected a duty cycle after get_duty".to_string()),
+        },
+        "get_max_duty" => match arguments {
+            [duty] => Expectation::Pwm(PwmExpectation::GetMaxDuty(parse_duty(duty)?)),
+            _ => return Err("expected a duty cycle after get_max_duty".to_string()),
+        },
+        "toggle_to" => {
+            let state = match arguments {
+                ["high"] => PinState::High,
+                ["low"] => PinState::Low,
+                _ => return Err("expected high or low after toggle_to".to_string()),
+            };
+
+            Expectation::OutputPin(OutputPinExpectation::ToggleTo(state))
+        }
+        _ => {
+            let expectation = match operation {
+                "is_high" => Expectation::InputPin(InputPinExpectation::IsHigh),
+                "is_low" => Expectation::InputPin(InputPinExpectation::IsLow),
+                "set_high" => Expectation::OutputPin(OutputPinExpectation::SetHigh),
+                "set_low" => Expectation::OutputPin(OutputPinExpectation::SetLow),
+                "is_set_high" => Expectation::OutputPin(OutputPinExpectation::IsSetHigh),
+                "is_set_low" => Expectation::OutputPin(OutputPinExpectation::IsSetLow),
+                "toggle" => Expectation::OutputPin(OutputPinExpectation::Toggle),
+                "expire" => Expectation::Timer(TimerExpectation::Expired),
+                "enable" => Expectation::Pwm(PwmExpectation::Enable),
+                "disable" => Expectation::Pwm(PwmExpectation::Disable),
+                _ => return Err(format!("unknown operation {}", operation)),
+            };
+
+            if !arguments.is_empty() {
+                return Err(format!("{} takes no arguments", operation));
+            }
+
+            expectation
+        }
+    };
+
+    Ok(TranscriptLine {
+        mock: mock.to_string(),
+        expectation,
+        times,
+    })
+}
+
+fn parse_times(times: &str) -> Result<Times, String> {
+    let parse = |value: &str| {
+        Some(value)
+            .filter(|value| is_digits(value))
+            .and_then(|value| value.parse::<usize>().ok())
+            .ok_or_else(|| format!("invalid times x{}", times))
+    };
+
+    if times == "*" {
+        Ok(Times::Any)
+    } else if let Some(value) = times.strip_prefix("..") {
+        Ok(Times::AtMost(parse(value)?))
+    } else if let Some(value) = times.strip_suffix('+') {
+        Ok(Times::AtLeast(parse(value)?))
+    } else {
+        Ok(Times::Exactly(parse(times)?))
+    }
+}
+
+fn parse_delay(delay: &str) -> Result<DelayExpectation, String> {
+    match delay.split_once("..") {
+        None => Ok(DelayExpectation::Exactly(parse_duration(delay)?)),
+        Some((min, "")) => Ok(DelayExpectation::AtLeast(parse_duration(min)?)),
+        Some(("", max)) => Ok(DelayExpectation::AtMost(parse_duration(max)?)),
+        Some((min, max)) => Ok(DelayExpectation::Between(
+            parse_duration(min)?,
+            parse_duration(max)?,
+        )),
+    }
+}
+
+/// `from_str_radix` also accepts a leading sign, which the format does not
+fn is_hex(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// `str::parse` also accepts a leading sign, which the format does not
+fn is_digits(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_digit())
+}
+
+fn parse_duty(duty: &str) -> Result<u16, String> {
+    Some(duty)
+        .filter(|duty| is_digits(duty))
+        .and_then(|duty| duty.parse::<u16>().ok())
+        .ok_or_else(|| format!("invalid duty cycle {}", duty))
+}
+
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let parse = |value: &str| {
+        Some(value)
+            .filter(|value| is_digits(value))
+            .and_then(|value| value.parse::<u64>().ok())
+            .ok_or_else(|| format!("invalid duration {}", duration))
+    };
+
+    if let Some(value) = duration.strip_suffix("ns") {
+        let nanos = Some(value)
+            .filter(|value| is_digits(value))
+            .and_then(|value| value.parse::<u128>().ok())
+            .and_then(|nanos| {
+                let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+                Some(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+            });
+
+        nanos.ok_or_else(|| format!("invalid duration {}", duration))
+    } else if let Some(value) = duration.strip_suffix("us") {
+        Ok(Duration::from_micros(parse(value)?))
+    } else if let Some(value) = duration.strip_suffix("ms") {
+        Ok(Duration::from_millis(parse(value)?))
+    } else if let Some(value) = duration.strip_suffix('s') {
+        Ok(Duration::from_secs(parse(value)?))
+    } else {
+        Err(format!(
+            "duration {} needs a unit of ns, us, ms or s",
+            duration
+        ))
+    }
+}
+
+fn parse_packet(arguments: &[&str]) -> Result<Packet, String> {
+    let (address, flag, data) = match arguments {
+        [address, flag, data @ ..] => (*address, *flag, data),
+        _ => return Err("expected a device address and ok or err".to_string()),
+    };
+
+    let device_address = address
+        .strip_prefix("0x")
+        .filter(|address| is_hex(address))
+        .and_then(|address| u16::from_str_radix(address, 16).ok())
+        .ok_or_else(|| format!("invalid device address {}", address))?;
+
+    let is_error = match flag {
+        "ok" => false,
+        "err" => true,
+        _ => return Err(format!("expected ok or err, found {}", flag)),
+    };
+
+    let data = data
+        .iter()
+        .map(|byte| match byte.len() {
+            2 if is_hex(byte) => u8::from_str_radix(byte, 16).ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| "data must be hex bytes separated by spaces".to_string())?;
+
+    Ok(Packet {
+        is_error,
+        device_address,
+        data,
+    })
+}
+
+fn parse_interface_error(arguments: &[&str]) -> Result<MockInterfaceError, String> {
+    let name = match arguments {
+        [name] => *name,
+        _ => return Err("expected an interface error name".to_string()),
+    };
+
+    INTERFACE_ERRORS
+        .iter()
+        .find(|(error_name, _)| *error_name == name)
+        .map(|(_, error)| *error)
+        .ok_or_else(|| format!("unknown interface error {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_hal::digital::v2::{InputPin, OutputPin};
+    use ross_protocol::interface::Interface;
+
+    const TRANSCRIPT: &str = "\
+# relay handshake
+relay recv 0x0001 ok 01 02
+relay send 0x0001 err
+relay recv_error NoPacketReceived x3
+
+button is_high x2+
+led set_high
+led toggle_to low x..1
+led is_set_low x*
+led delay 1500us..2ms
+led delay 250ns..
+timer start 10ms
+timer expire x2
+dimmer set_duty 10..20 x*
+";
+
+    #[test]
+    fn parse_test() {
+        let transcript = Transcript::parse(TRANSCRIPT).unwrap();
+
+        assert_eq!(transcript.lines().len(), 12);
+        assert_eq!(
+            transcript.lines()[0],
+            TranscriptLine {
+                mock: "relay".to_string(),
+                expectation: Expectation::Interface(InterfaceExpectation::ReceivedPacket(Packet {
+                    is_error: false,
+                    device_address: 0x0001,
+                    data: vec![0x01, 0x02],
+                })),
+                times: Times::Exactly(1),
+            }
+        );
+        assert_eq!(
+            transcript.lines()[2].expectation,
+            Expectation::Interface(InterfaceExpectation::ReceiveError(
+                MockInterfaceError::NoPacketReceived
+            ))
+        );
+        assert_eq!(transcript.lines()[2].times, Times::Exactly(3));
+        assert_eq!(transcript.lines()[3].times, Times::AtLeast(2));
+        assert_eq!(transcript.lines()[5].times, Times::AtMost(1));
+        assert_eq!(transcript.lines()[6].times, Times::Any);
+        assert_eq!(
+            transcript.lines()[7].expectation,
+            Expectation::Delay(DelayExpectation::Between(
+                Duration::from_micros(1500),
+                Duration::from_millis(2)
+            ))
+        );
+        assert_eq!(
+            transcript.lines()[8].expectation,
+            Expectation::Delay(DelayExpectation::AtLeast(Duration::from_nanos(250)))
+        );
+        assert_eq!(
+            transcript.lines()[9].expectation,
+            Expectation::Timer(TimerExpectation::Started(Duration::from_millis(10)))
+        );
+        assert_eq!(
+            transcript.lines()[10].expectation,
+            Expectation::Timer(TimerExpectation::Expired)
+        );
+        assert_eq!(
+            transcript.lines()[11].expectation,
+            Expectation::Pwm(PwmExpectation::SetDutyBetween(10, 20))
+        );
+    }
+
+    #[test]
+    fn serialize_round_trip_test() {
+        let transcript = Transcript::parse(TRANSCRIPT).unwrap();
+        let text = transcript.to_string();
+
+        assert_eq!(text.lines().next(), Some("relay recv 0x0001 ok 01 02"));
+        assert_eq!(Transcript::parse(&text).unwrap(), transcript);
+    }
+
+    #[test]
+    fn nanosecond_round_trip_test() {
+        let mut transcript = Transcript::new();
+
+        for duration in [
+            Duration::from_nanos(1500),
+            Duration::new(2, 1),
+            Duration::from_micros(1500),
+        ] {
+            transcript
+                .push(
+                    "led",
+                    Expectation::Delay(DelayExpectation::Exactly(duration)),
+                    Times::Exactly(1),
+                )
+                .unwrap();
+        }
+
+        let text = transcript.to_string();
+
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            vec![
+                "led delay 1500ns",
+                "led delay 2000000001ns",
+                "led delay 1500us"
+            ]
+        );
+        assert_eq!(Transcript::parse(&text).unwrap(), transcript);
+    }
+
+    #[test]
+    fn syntax_error_test() {
+        assert_eq!(
+            Transcript::parse("relay send 0x0001 ok 01\nrelay send 0x0001 ok 1"),
+            Err(TranscriptError::Syntax(
+                2,
+                "data must be hex bytes separated by spaces".to_string()
+            ))
+        );
+        assert_eq!(
+            Transcript::parse("led blink"),
+            Err(TranscriptError::Syntax(
+                1,
+                "unknown operation blink".to_string()
+            ))
+        );
+        assert_eq!(
+            Transcript::parse("relay send 0x+1 ok 01"),
+            Err(TranscriptError::Syntax(
+                1,
+                "invalid device address 0x+1".to_string()
+            ))
+        );
+        assert_eq!(
+            Transcript::parse("d set_duty +5"),
+            Err(TranscriptError::Syntax(
+                1,
+                "invalid duty cycle +5".to_string()
+            ))
+        );
+        assert_eq!(
+            Transcript::parse("b is_high x+2"),
+            Err(TranscriptError::Syntax(1, "invalid times x+2".to_string()))
+        );
+        assert_eq!(
+            Transcript::parse("led delay +10ms"),
+            Err(TranscriptError::Syntax(
+                1,
+                "invalid duration +10ms".to_string()
+            ))
+        );
+        assert_eq!(
+            Transcript::parse("relay send 0x0001 ok +1"),
+            Err(TranscriptError::Syntax(
+                1,
+                "data must be hex bytes separated by spaces".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_mock_name_test() {
+        let mut transcript = Transcript::new();
+
+        for name in ["my relay", "#relay", ""] {
+            assert_eq!(
+                transcript.push(
+                    name,
+                    Expectation::OutputPin(OutputPinExpectation::SetHigh),
+                    Times::Exactly(1)
+                ),
+                Err(TranscriptError::InvalidMockName(name.to_string()))
+            );
+        }
+
+        let tracker = ExpectationTracker::new();
+        let relay = ExpectationTracker::named_mock(tracker.clone(), "my relay");
+        relay.expect().set_high();
+
+        assert_eq!(
+            Transcript::from_tracker(&tracker.borrow()),
+            Err(TranscriptError::InvalidMockName("my relay".to_string()))
+        );
+    }
+
+    #[test]
+    fn load_test() {
+        let tracker = ExpectationTracker::new();
+        let mut relay = ExpectationTracker::named_mock(tracker.clone(), "relay");
+        let button = ExpectationTracker::named_mock(tracker.clone(), "button");
+        let mut led = ExpectationTracker::named_mock(tracker.clone(), "led");
+
+        Transcript::parse(
+            "relay recv 0x0001 ok 01\nbutton is_high x2\nled set_high\nrelay send 0x0002 ok",
+        )
+        .unwrap()
+        .load(tracker.clone())
+        .unwrap();
+
+        assert_eq!(relay.try_get_packet().unwrap().data, vec![0x01]);
+        assert!(button.is_high().unwrap());
+        assert!(button.is_high().unwrap());
+        led.set_high().unwrap();
+        relay
+            .try_send_packet(&Packet {
+                is_error: false,
+                device_address: 0x0002,
+                data: vec![],
+            })
+            .unwrap();
+
+        tracker.borrow_mut().done();
+    }
+
+    #[test]
+    fn load_unknown_mock_test() {
+        let tracker = ExpectationTracker::new();
+        ExpectationTracker::named_mock(tracker.clone(), "relay");
+
+        assert_eq!(
+            Transcript::parse("relay set_high\nled set_high")
+                .unwrap()
+                .load(tracker.clone()),
+            Err(TranscriptError::UnknownMock("led".to_string()))
+        );
+        assert_eq!(tracker.borrow().entries().count(), 0);
+    }
+
+    #[test]
+    fn from_tracker_test() {
+        let tracker = ExpectationTracker::new();
+        let led = ExpectationTracker::named_mock(tracker.clone(), "led");
+        let pin = ExpectationTracker::mock(tracker.clone());
+
+        ExpectationTracker::expect_times(
+            tracker.clone(),
+            &led,
+            Expectation::OutputPin(OutputPinExpectation::Toggle),
+            Times::Exactly(4),
+        );
+
+        assert_eq!(
+            Transcript::from_tracker(&tracker.borrow())
+                .unwrap()
+                .to_string(),
+            "led toggle x4\n"
+        );
+
+        ExpectationTracker::expect(
+            tracker.clone(),
+            &pin,
+            Expectation::OutputPin(OutputPinExpectation::SetLow),
+        );
+
+        assert_eq!(
+            Transcript::from_tracker(&tracker.borrow()),
+            Err(TranscriptError::UnnamedMock(1))
+        );
+    }
+}
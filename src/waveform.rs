@@ -0,0 +1,99 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::time::Duration;
+use embedded_hal::digital::v2::PinState;
+
+/// Input level held for a duration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaveformSegment {
+    pub level: PinState,
+    pub duration: Duration,
+}
+
+/// Input pin stimulus as a sequence of levels on the virtual clock
+///
+/// The level of the last segment is held once the waveform is over.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform {
+    segments: Vec<WaveformSegment>,
+}
+
+impl Waveform {
+    /// Creates a waveform that starts and, without segments, stays at the given level
+    pub fn new(level: PinState) -> Self {
+        Self {
+            segments: vec![WaveformSegment {
+                level,
+                duration: Duration::ZERO,
+            }],
+        }
+    }
+
+    pub fn high(self, duration: Duration) -> Self {
+        self.segment(PinState::High, duration)
+    }
+
+    pub fn low(self, duration: Duration) -> Self {
+        self.segment(PinState::Low, duration)
+    }
+
+    pub fn segment(mut self, level: PinState, duration: Duration) -> Self {
+        self.segments.push(WaveformSegment { level, duration });
+        self
+    }
+
+    pub fn segments(&self) -> &[WaveformSegment] {
+        &self.segments
+    }
+
+    /// Total duration of all segments
+    pub fn duration(&self) -> Duration {
+        self.segments.iter().map(|segment| segment.duration).sum()
+    }
+
+    /// Level at the given time since the start of the waveform
+    pub fn level_at(&self, time: Duration) -> PinState {
+        let mut end = Duration::ZERO;
+
+        for segment in self.segments.iter() {
+            end += segment.duration;
+
+            if time < end {
+                return segment.level;
+            }
+        }
+
+        self.segments.last().unwrap().level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_at_test() {
+        let waveform = Waveform::new(PinState::Low)
+            .low(Duration::from_millis(10))
+            .high(Duration::from_millis(300))
+            .low(Duration::from_millis(5));
+
+        assert_eq!(waveform.duration(), Duration::from_millis(315));
+        assert_eq!(waveform.level_at(Duration::ZERO), PinState::Low);
+        assert_eq!(waveform.level_at(Duration::from_millis(10)), PinState::High);
+        assert_eq!(
+            waveform.level_at(Duration::from_millis(309)),
+            PinState::High
+        );
+        assert_eq!(waveform.level_at(Duration::from_millis(310)), PinState::Low);
+        assert_eq!(waveform.level_at(Duration::from_secs(10)), PinState::Low);
+    }
+
+    #[test]
+    fn constant_level_test() {
+        let waveform = Waveform::new(PinState::High);
+
+        assert_eq!(waveform.duration(), Duration::ZERO);
+        assert_eq!(waveform.level_at(Duration::from_secs(1)), PinState::High);
+    }
+}